@@ -118,6 +118,25 @@ pub enum Commands {
         password: String,
     },
 
+    #[clap(about = "Change the password of the snapshot.")]
+    Rekey {
+        #[clap(
+            long = "pass",
+            short = 'w',
+            required = true,
+            help = "The current password for the snapshot."
+        )]
+        password: String,
+
+        #[clap(
+            long = "new_pass",
+            short = 'n',
+            required = true,
+            help = "The new password for the snapshot."
+        )]
+        new_password: String,
+    },
+
     #[clap(
         alias = "take_ownership",
         about = "Take ownership of an existing chain to give it to a new user."
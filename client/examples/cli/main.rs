@@ -5,7 +5,7 @@ mod arguments;
 
 use arguments::*;
 use clap::Parser;
-use iota_stronghold::{home_dir, naive_kdf, Location, RecordHint, Stronghold};
+use iota_stronghold::{home_dir, naive_kdf, Location, RecordHint, SnapshotKey, Stronghold};
 use std::{
     error::Error,
     path::{Path, PathBuf},
@@ -369,6 +369,27 @@ async fn purge_command(
     Ok(())
 }
 
+/// Changes the password of the snapshot, without loading it into the stronghold clients.  Requires the current and the
+/// new password.
+async fn rekey_command(pass: &str, new_pass: &str) -> Result<(), Box<dyn Error>> {
+    let salt = [0u8; 32];
+    let mut key = [0u8; 32];
+    naive_kdf(pass.as_bytes(), &salt, &mut key);
+    let mut new_key = [0u8; 32];
+    naive_kdf(new_pass.as_bytes(), &salt, &mut new_key);
+
+    let snapshot = home_dir()?.join("snapshots").join("commandline.stronghold");
+
+    if snapshot.exists() {
+        Stronghold::rekey_snapshot(&snapshot, &SnapshotKey::Key(key), &SnapshotKey::Key(new_key))?;
+    } else {
+        return Err(Box::from(
+            "Could not find a snapshot at the home path.  Try writing first.",
+        ));
+    }
+    Ok(())
+}
+
 async fn take_ownership_command(
     _password: &str,
     _stronghold: &mut Stronghold,
@@ -417,6 +438,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Commands::Snapshot { path, pass } => {
             snapshot_command(pass.as_str(), path.as_str(), &mut stronghold, client_path).await
         }
+        Commands::Rekey { password, new_password } => rekey_command(password.as_str(), new_password.as_str()).await,
         Commands::TakeOwnership { password } => {
             take_ownership_command(password.as_str(), &mut stronghold, client_path).await
         }
@@ -15,7 +15,10 @@ pub use self::{
 };
 pub use self::{
     registry::{
-        messages::{GetAllClients, GetClient, GetSnapshot, GetTarget, RemoveClient, SpawnClient, SwitchTarget},
+        messages::{
+            GetAllClients, GetClient, GetSnapshot, GetTarget, RegisterProcedure, RemoveClient, SpawnClient,
+            SwitchTarget,
+        },
         Registry,
     },
     secure::{messages as secure_messages, RecordError, VaultError},
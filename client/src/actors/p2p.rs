@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    actors::{secure_messages::WriteToVault, GetClient},
+    actors::{client_p2p_messages::RemoteRequest, secure_messages::WriteToVault, GetClient},
     state::p2p::{Network, NetworkConfig, Request, ShRequest, ShResult},
     utils::LoadFromPath,
 };
@@ -57,15 +57,18 @@ impl Actor for Network {
 impl StreamHandler<ReceiveRequest<ShRequest, ShResult>> for Network {
     fn handle(&mut self, item: ReceiveRequest<ShRequest, ShResult>, ctx: &mut Self::Context) {
         let ReceiveRequest {
-            request, response_tx, ..
+            request,
+            response_tx,
+            peer,
+            ..
         } = item;
         let ShRequest { client_path, request } = request;
         let client_id = ClientId::load_from_path(&client_path, &client_path);
         sh_request_dispatch!(request => |inner| {
             let fut = self.registry
                 .send(GetClient {id: client_id})
-                .and_then(|client| async { match client {
-                    Some(client) => client.send(inner).await,
+                .and_then(move |client| async move { match client {
+                    Some(client) => client.send(RemoteRequest { peer, request: inner }).await,
                     _ => Err(MailboxError::Closed)
                 }})
                 .map_ok(|response| response_tx.send(response.into()))
@@ -14,7 +14,10 @@ use std::collections::HashMap;
 
 #[cfg(feature = "p2p")]
 use crate::state::p2p::Network;
-use crate::state::{secure::SecureClient, snapshot::Snapshot};
+use crate::{
+    procedures::{ProcedureRegistration, ProcedureRegistry},
+    state::{secure::SecureClient, snapshot::Snapshot},
+};
 
 pub mod messages {
     use super::*;
@@ -68,6 +71,14 @@ pub mod messages {
     impl Message for GetAllClients {
         type Result = Vec<(ClientId, Addr<SecureClient>)>;
     }
+
+    pub struct RegisterProcedure {
+        pub registration: ProcedureRegistration,
+    }
+
+    impl Message for RegisterProcedure {
+        type Result = ();
+    }
 }
 
 #[cfg(feature = "p2p")]
@@ -103,6 +114,7 @@ pub struct Registry {
     clients: HashMap<ClientId, Addr<SecureClient>>,
     current_target: Option<ClientId>,
     snapshot: Option<Addr<Snapshot>>,
+    custom_procedures: ProcedureRegistry,
     #[cfg(feature = "p2p")]
     network: Option<Addr<Network>>,
 }
@@ -120,7 +132,9 @@ impl Handler<messages::SpawnClient> for Registry {
         if let Some(addr) = self.clients.get(&msg.id) {
             return addr.clone();
         }
-        let addr = SecureClient::new(msg.id).start();
+        let mut client = SecureClient::new(msg.id);
+        client.custom_procedures = self.custom_procedures.clone();
+        let addr = client.start();
         self.clients.insert(msg.id, addr);
 
         Self::handle(self, messages::SwitchTarget { id: msg.id }, ctx).unwrap()
@@ -182,6 +196,18 @@ impl Handler<messages::GetAllClients> for Registry {
     }
 }
 
+impl Handler<messages::RegisterProcedure> for Registry {
+    type Result = ();
+
+    fn handle(&mut self, msg: messages::RegisterProcedure, _: &mut Self::Context) -> Self::Result {
+        let ProcedureRegistration { name, executor } = msg.registration;
+        // The lock is only held for single map operations, so it can not be poisoned.
+        if let Ok(mut procedures) = self.custom_procedures.write() {
+            procedures.insert(name, executor);
+        }
+    }
+}
+
 #[cfg(feature = "p2p")]
 impl Handler<p2p_messages::InsertNetwork> for Registry {
     type Result = ();
@@ -10,24 +10,34 @@
 
 use crate::{
     internals::Provider,
-    procedures::{Procedure, ProcedureError, ProcedureOutput, Runner},
-    state::secure::SecureClient,
+    procedures::{FatalProcedureError, ProcedureError, ProcedureOutput, Runner},
+    state::{
+        audit::{AuditEntry, AuditError, AuditOperation, AuditOutcome, AuditProcedure, AuditQuery, AUDIT_HASH_LEN},
+        secure::{ClientState, SecureClient},
+    },
+    RecordInfo,
+};
+use actix::{
+    Actor, ActorContext, ActorFutureExt, Context, Handler, Message, MessageResult, ResponseActFuture, Supervised,
+    WrapFuture,
 };
-use actix::{Actor, ActorContext, Context, Handler, Message, MessageResult, Supervised};
 use engine::{
     store::Cache,
     vault::{
-        BoxProvider, ClientId, DbView, Key, RecordError as EngineRecordError, RecordHint, RecordId,
+        BoxProvider, ClientId, DbView, Key, RecordError as EngineRecordError, RecordHint,
         VaultError as EngineVaultError, VaultId,
     },
 };
 
+#[cfg(feature = "p2p")]
+use crate::procedures::ProcedureKind;
 #[cfg(feature = "p2p")]
 use engine::runtime::GuardedVec;
 #[cfg(feature = "p2p")]
 use p2p::{identity::Keypair, AuthenticKeypair, NoiseKeypair, PeerId};
 use std::collections::HashMap;
 use stronghold_utils::GuardDebug;
+use tokio::{task, time};
 
 /// Store typedef on `engine::store::Cache`
 pub type Store = Cache<Vec<u8>, Vec<u8>>;
@@ -39,7 +49,11 @@ pub type RecordError = EngineRecordError<<Provider as BoxProvider>::Error>;
 pub mod messages {
 
     use super::*;
-    use crate::{internals, procedures::StrongholdProcedure, Location};
+    use crate::{
+        internals,
+        procedures::{CancellationToken, ChainInput, StrongholdProcedure},
+        Location, VaultFlags,
+    };
     use serde::{Deserialize, Serialize};
     use std::time::Duration;
 
@@ -57,6 +71,7 @@ pub mod messages {
             HashMap<VaultId, Key<internals::Provider>>,
             DbView<internals::Provider>,
             Store,
+            ClientState,
         )>,
     }
 
@@ -69,10 +84,11 @@ pub mod messages {
 
         pub payload: Vec<u8>,
         pub hint: RecordHint,
+        pub options: Vec<VaultFlags>,
     }
 
     impl Message for WriteToVault {
-        type Result = Result<(), RecordError>;
+        type Result = Result<(), ProcedureError>;
     }
 
     #[derive(Clone, GuardDebug, Serialize, Deserialize)]
@@ -93,13 +109,22 @@ pub mod messages {
         type Result = bool;
     }
 
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct RotateVaultKey {
+        pub vault_path: Vec<u8>,
+    }
+
+    impl Message for RotateVaultKey {
+        type Result = Result<bool, RecordError>;
+    }
+
     #[derive(Clone, GuardDebug, Serialize, Deserialize)]
     pub struct ListIds {
         pub vault_path: Vec<u8>,
     }
 
     impl Message for ListIds {
-        type Result = Vec<(RecordId, RecordHint)>;
+        type Result = Vec<RecordInfo>;
     }
 
     #[derive(Clone, GuardDebug, Serialize, Deserialize)]
@@ -163,22 +188,47 @@ pub mod messages {
             HashMap<VaultId, Key<internals::Provider>>,
             DbView<internals::Provider>,
             Store,
+            ClientState,
         )>;
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct Procedures {
         pub procedures: Vec<StrongholdProcedure>,
+        pub inputs: Vec<ChainInput>,
+        pub timeout: Option<Duration>,
+        // A token can only be used within the local Stronghold.
+        #[serde(skip)]
+        pub cancellation: Option<CancellationToken>,
     }
 
     impl Message for Procedures {
         type Result = Result<Vec<ProcedureOutput>, ProcedureError>;
     }
 
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct ReadAuditLog {
+        pub query: AuditQuery,
+    }
+
+    impl Message for ReadAuditLog {
+        type Result = Vec<AuditEntry>;
+    }
+
+    #[derive(Clone, GuardDebug, Serialize, Deserialize)]
+    pub struct VerifyAuditLog;
+
+    impl Message for VerifyAuditLog {
+        type Result = Result<[u8; AUDIT_HASH_LEN], AuditError>;
+    }
+
     impl<T: Into<StrongholdProcedure>> From<T> for Procedures {
         fn from(proc: T) -> Self {
             Procedures {
                 procedures: vec![proc.into()],
+                inputs: Vec::new(),
+                timeout: None,
+                cancellation: None,
             }
         }
     }
@@ -223,6 +273,17 @@ pub mod p2p_messages {
     impl Message for DeriveNoiseKeypair {
         type Result = Result<(PeerId, AuthenticKeypair), ProcedureError>;
     }
+
+    /// Request that was received from a remote peer.  The `PeerId` of the peer is recorded in the audit log
+    /// entries of the wrapped request.
+    pub struct RemoteRequest<M> {
+        pub peer: PeerId,
+        pub request: M,
+    }
+
+    impl<M: Message> Message for RemoteRequest<M> {
+        type Result = M::Result;
+    }
 }
 
 /// Functional macro to remove boilerplate code for the implementation
@@ -298,8 +359,13 @@ impl_handler!(messages::Terminate, (), (self, _msg, ctx), {
 });
 
 impl_handler!(messages::ClearCache, (), (self, _msg, _ctx), {
+    let (audit_log, audit_key) = self.take_audit_log();
     self.keystore.clear_keys();
     self.db.clear();
+    self.client_state = ClientState::default();
+    self.revisions.reset();
+    self.restore_audit_log(audit_log, audit_key);
+    self.audit(AuditOperation::ClearCache, AuditOutcome::Success);
 });
 
 impl_handler!(messages::CheckRecord, bool, (self, msg, _ctx), {
@@ -315,36 +381,62 @@ impl_handler!(messages::CheckRecord, bool, (self, msg, _ctx), {
     };
 });
 
-impl_handler!(messages::WriteToVault, Result<(), RecordError>, (self, msg, _ctx), {
-    self.write_to_vault(&msg.location, msg.hint, msg.payload)
+impl_handler!(messages::WriteToVault, Result<(), ProcedureError>, (self, msg, _ctx), {
+    let (vault_id, _) = Self::resolve_location(&msg.location);
+    self.init_vault_policy(vault_id, msg.options);
+    let res = self.write_to_vault(&msg.location, msg.hint, None, msg.payload);
+    self.audit(AuditOperation::Write(msg.location), &res);
+    res
 });
 
 impl_handler!(messages::RevokeData, Result<(), RecordError>, (self, msg, _ctx), {
-    self.revoke_data(&msg.location)
+    let res = self.revoke_data(&msg.location);
+    self.audit(AuditOperation::Revoke(msg.location), &res);
+    res
 });
 
 impl_handler!(messages::GarbageCollect, bool, (self, msg, _ctx), {
-    let (vault_id, _) = Self::resolve_location(msg.location);
-    self.garbage_collect(vault_id)
+    let (vault_id, _) = Self::resolve_location(&msg.location);
+    let res = self.garbage_collect(vault_id);
+    let outcome = match res {
+        true => AuditOutcome::Success,
+        false => AuditOutcome::Failure("vault does not exist".into()),
+    };
+    self.audit(
+        AuditOperation::GarbageCollect(msg.location.vault_path().to_vec()),
+        outcome,
+    );
+    res
 });
 
-impl_handler!(messages::ListIds, Vec<(RecordId, RecordHint)>, (self, msg, _ctx), {
-    let vault_id = Self::derive_vault_id(msg.vault_path);
-    let key = match self.keystore.take_key(vault_id) {
-        Some(k) => k,
-        None => return Vec::new(),
+impl_handler!(messages::RotateVaultKey, Result<bool, RecordError>, (self, msg, _ctx), {
+    let vault_id = Self::derive_vault_id(&msg.vault_path);
+    let res = self.rotate_vault_key(vault_id);
+    let outcome = match res {
+        Ok(true) => AuditOutcome::Success,
+        Ok(false) => AuditOutcome::Failure("vault does not exist".into()),
+        Err(ref e) => AuditOutcome::Failure(e.to_string()),
     };
+    self.audit(AuditOperation::RotateVaultKey(msg.vault_path), outcome);
+    res
+});
 
-    let list = self.db.list_hints_and_ids(&key, vault_id);
-    self.keystore.insert_key(vault_id, key);
-    list
+impl_handler!(messages::ListIds, Vec<RecordInfo>, (self, msg, _ctx), {
+    let vault_id = Self::derive_vault_id(msg.vault_path);
+    self.list_records(vault_id)
 });
 
 impl_handler!(messages::ReloadData, (), (self, msg, _ctx), {
-    let (keystore, state, store) = *msg.data;
+    let (keystore, state, store, client_state) = *msg.data;
+    let (audit_log, _) = self.take_audit_log();
     self.keystore.rebuild_keystore(keystore);
     self.db = state;
+    self.client_state = client_state;
+    self.revisions.reset();
     self.rebuild_cache(self.client_id, store);
+    // Operations that the client recorded before are appended to the log of the snapshot.
+    self.merge_audit_log(audit_log);
+    self.audit(AuditOperation::LoadSnapshot, AuditOutcome::Success);
 });
 
 impl_handler!(messages::CheckVault, bool, (self, msg, _ctx), {
@@ -353,15 +445,31 @@ impl_handler!(messages::CheckVault, bool, (self, msg, _ctx), {
 });
 
 impl_handler!(messages::WriteToStore, Option<Vec<u8>>, (self, msg, _ctx), {
+    self.audit(AuditOperation::StoreWrite(msg.key.clone()), AuditOutcome::Success);
     self.write_to_store(msg.key, msg.payload, msg.lifetime)
 });
 
 impl_handler!(messages::ReadFromStore, Option<Vec<u8>>, (self, msg, _ctx), {
-    self.read_from_store(msg.key)
+    let res = self.read_from_store(msg.key.clone());
+    let outcome = match res {
+        Some(_) => AuditOutcome::Success,
+        None => AuditOutcome::Failure("key does not exist".into()),
+    };
+    self.audit(AuditOperation::StoreRead(msg.key), outcome);
+    res
 });
 
 impl_handler!(messages::DeleteFromStore, (), (self, msg, _ctx), {
-    self.store_delete_item(msg.key);
+    self.store_delete_item(msg.key.clone());
+    self.audit(AuditOperation::StoreDelete(msg.key), AuditOutcome::Success);
+});
+
+impl_handler!(messages::ReadAuditLog, Vec<AuditEntry>, (self, msg, _ctx), {
+    self.client_state.audit_log.query(&msg.query)
+});
+
+impl_handler!(messages::VerifyAuditLog, Result<[u8; AUDIT_HASH_LEN], AuditError>, (self, _msg, _ctx), {
+    self.verify_audit_log()
 });
 
 impl_handler!(
@@ -372,34 +480,70 @@ impl_handler!(
         let keystore = self.keystore.get_data();
         let dbview = self.db.clone();
         let store = self.store.clone();
+        let client_state = self.client_state.clone();
 
-        MessageResult(Box::from((keystore, dbview, store)))
+        MessageResult(Box::from((keystore, dbview, store, client_state)))
     }
 );
 
 impl Handler<messages::Procedures> for SecureClient {
-    type Result = Result<Vec<ProcedureOutput>, ProcedureError>;
+    type Result = ResponseActFuture<Self, Result<Vec<ProcedureOutput>, ProcedureError>>;
 
+    /// Executes the procedures on a staging copy of the vaults in the blocking thread pool, so that long-running
+    /// procedures do not block the actor.  The client handles other messages in the meantime.  Once the chain has
+    /// finished the copy is committed, unless a vault that it changed was also changed by another message in the
+    /// meantime.
     fn handle(&mut self, msg: messages::Procedures, _: &mut Self::Context) -> Self::Result {
-        let mut out = Vec::new();
-        let mut log = Vec::new();
-        // Execute the procedures sequentially.
-        for proc in msg.procedures {
-            if let Some(output) = proc.output() {
-                log.push(output);
-            }
-            let output = match proc.execute(self) {
-                Ok(o) => o,
-                Err(e) => {
-                    for location in log {
-                        let _ = self.revoke_data(&location);
-                    }
-                    return Err(e);
+        let procedures = msg
+            .procedures
+            .iter()
+            .map(|proc| AuditProcedure {
+                name: proc.name().into(),
+                input: proc.input(),
+                output: proc.output(),
+            })
+            .collect();
+        let peer = self.remote_peer.clone();
+
+        let messages::Procedures {
+            procedures: chain,
+            inputs,
+            timeout,
+            cancellation,
+        } = msg;
+        let cancellation = cancellation.unwrap_or_default();
+        let mut staging = self.staging(&chain);
+        let token = cancellation.clone();
+        let job = task::spawn_blocking(move || -> Result<_, ProcedureError> {
+            let outputs = staging.client.execute_procedures(chain, inputs, &token)?;
+            Ok((staging, outputs))
+        });
+        let execution = async move {
+            let run = async {
+                tokio::select! {
+                    res = job => res.unwrap_or_else(|e| Err(FatalProcedureError::from(e.to_string()).into())),
+                    _ = cancellation.cancelled() => Err(ProcedureError::Cancelled),
                 }
             };
-            out.push(output);
-        }
-        Ok(out)
+            let res = match timeout {
+                Some(timeout) => time::timeout(timeout, run)
+                    .await
+                    .unwrap_or(Err(ProcedureError::TimedOut)),
+                None => run.await,
+            };
+            // Stop the chain before its next procedure if its result is no longer awaited.
+            if res.is_err() {
+                cancellation.cancel();
+            }
+            res
+        };
+        let fut = execution.into_actor(self).map(move |res, act, _| {
+            let res = res.and_then(|(staging, outputs)| act.commit(staging).map(|()| outputs));
+            let outcome = AuditOutcome::from(&res);
+            act.append_audit_entry(AuditOperation::Procedures(procedures), outcome, peer);
+            res
+        });
+        Box::pin(fut)
     }
 }
 
@@ -412,8 +556,9 @@ impl Handler<p2p_messages::GenerateP2pKeypair> for SecureClient {
         let bytes = keypair
             .to_protobuf_encoding()
             .map_err(|e| ProcedureError::Procedure(e.to_string().into()))?;
-        self.write_to_vault(&msg.location, msg.hint, bytes)?;
-        Ok(())
+        let res = self.write_to_vault(&msg.location, msg.hint, None, bytes);
+        self.audit(AuditOperation::Write(msg.location), &res);
+        res
     }
 }
 
@@ -426,8 +571,9 @@ impl Handler<p2p_messages::WriteP2pKeypair> for SecureClient {
             .keypair
             .to_protobuf_encoding()
             .map_err(|e| ProcedureError::Procedure(e.to_string().into()))?;
-        self.write_to_vault(&msg.location, msg.hint, bytes)?;
-        Ok(())
+        let res = self.write_to_vault(&msg.location, msg.hint, None, bytes);
+        self.audit(AuditOperation::Write(msg.location), &res);
+        res
     }
 }
 
@@ -442,7 +588,7 @@ impl Handler<p2p_messages::DeriveNoiseKeypair> for SecureClient {
             let _ = id_keys.insert(keys);
             Ok(())
         };
-        self.get_guard(&msg.p2p_keypair, f)?;
+        self.get_guard(&msg.p2p_keypair, ProcedureKind::Use, f)?;
         let id_keys = id_keys.unwrap();
         let keypair = NoiseKeypair::new()
             .into_authentic(&id_keys)
@@ -451,3 +597,39 @@ impl Handler<p2p_messages::DeriveNoiseKeypair> for SecureClient {
         Ok((peer_id, keypair))
     }
 }
+
+/// Implements the handler of [`p2p_messages::RemoteRequest`] for a request that can be sent by remote peers. The
+/// request is handled like a local one, while the peer is set as remote peer of the client.
+#[cfg(feature = "p2p")]
+macro_rules! impl_remote_handler {
+    ($($(#[$attr:meta])* $mty:ty),*) => {
+        $(
+            $(#[$attr])*
+            impl Handler<p2p_messages::RemoteRequest<$mty>> for SecureClient {
+                type Result = <SecureClient as Handler<$mty>>::Result;
+
+                fn handle(&mut self, msg: p2p_messages::RemoteRequest<$mty>, ctx: &mut Self::Context) -> Self::Result {
+                    self.remote_peer = Some(msg.peer.to_base58());
+                    let res = <Self as Handler<$mty>>::handle(self, msg.request, ctx);
+                    self.remote_peer = None;
+                    res
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "p2p")]
+impl_remote_handler!(
+    messages::CheckVault,
+    messages::CheckRecord,
+    messages::WriteToStore,
+    messages::ReadFromStore,
+    messages::DeleteFromStore,
+    messages::WriteToVault,
+    #[cfg(test)]
+    testing::ReadFromVault,
+    messages::RevokeData,
+    messages::ListIds,
+    messages::Procedures
+);
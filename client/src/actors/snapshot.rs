@@ -8,14 +8,14 @@ use actix::{Actor, Handler, Message, Supervised};
 use std::path::PathBuf;
 
 use engine::{
-    snapshot,
+    snapshot::SnapshotKey,
     vault::{ClientId, DbView, Key, VaultId},
 };
 
 use crate::{
     internals,
     state::{
-        secure::Store,
+        secure::{ClientState, Store},
         snapshot::{ReadError, Snapshot, SnapshotState, WriteError},
     },
     Provider,
@@ -38,6 +38,7 @@ pub mod returntypes {
             HashMap<VaultId, Key<internals::Provider>>,
             DbView<internals::Provider>,
             Store,
+            ClientState,
         )>,
     }
 }
@@ -47,7 +48,7 @@ pub mod messages {
     use super::*;
 
     pub struct WriteSnapshot {
-        pub key: snapshot::Key,
+        pub key: SnapshotKey,
         pub filename: Option<String>,
         pub path: Option<PathBuf>,
     }
@@ -57,7 +58,7 @@ pub mod messages {
     }
 
     pub struct FillSnapshot {
-        pub data: Box<(HashMap<VaultId, Key<Provider>>, DbView<Provider>, Store)>,
+        pub data: Box<(HashMap<VaultId, Key<Provider>>, DbView<Provider>, Store, ClientState)>,
         pub id: ClientId,
     }
 
@@ -65,9 +66,16 @@ pub mod messages {
         type Result = ();
     }
 
-    #[derive(Default)]
+    pub struct SetSnapshotLabel {
+        pub label: Option<String>,
+    }
+
+    impl Message for SetSnapshotLabel {
+        type Result = ();
+    }
+
     pub struct ReadFromSnapshot {
-        pub key: snapshot::Key,
+        pub key: SnapshotKey,
         pub filename: Option<String>,
         pub path: Option<PathBuf>,
         pub id: ClientId,
@@ -94,6 +102,14 @@ impl Handler<messages::FillSnapshot> for Snapshot {
     }
 }
 
+impl Handler<messages::SetSnapshotLabel> for Snapshot {
+    type Result = ();
+
+    fn handle(&mut self, msg: messages::SetSnapshotLabel, _ctx: &mut Self::Context) -> Self::Result {
+        self.label = msg.label;
+    }
+}
+
 impl Handler<messages::ReadFromSnapshot> for Snapshot {
     type Result = Result<returntypes::ReturnReadSnapshot, ReadError>;
 
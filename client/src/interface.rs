@@ -11,25 +11,36 @@ use crate::{
     actors::{
         secure_messages::{
             CheckRecord, CheckVault, ClearCache, DeleteFromStore, GarbageCollect, GetData, ListIds, Procedures,
-            ReadFromStore, ReloadData, RevokeData, WriteToStore, WriteToVault,
+            ReadAuditLog, ReadFromStore, ReloadData, RevokeData, RotateVaultKey, VerifyAuditLog, WriteToStore,
+            WriteToVault,
         },
-        snapshot_messages::{FillSnapshot, ReadFromSnapshot, WriteSnapshot},
-        GetAllClients, GetClient, GetSnapshot, GetTarget, RecordError, Registry, RemoveClient, SpawnClient,
-        SwitchTarget,
+        snapshot_messages::{FillSnapshot, ReadFromSnapshot, SetSnapshotLabel, WriteSnapshot},
+        GetAllClients, GetClient, GetSnapshot, GetTarget, RecordError, RegisterProcedure, Registry, RemoveClient,
+        SpawnClient, SwitchTarget,
+    },
+    procedures::{
+        ChainInput, ExecutionOptions, FatalProcedureError, Procedure, ProcedureError, ProcedureOutput,
+        ProcedureRegistration, StrongholdProcedure,
     },
-    procedures::{Procedure, ProcedureError, ProcedureOutput, StrongholdProcedure},
     state::{
+        audit::{AuditEntry, AuditError, AuditQuery, AUDIT_HASH_LEN},
         secure::SecureClient,
-        snapshot::{ReadError, WriteError},
+        snapshot::{ReadError, RekeyError, Snapshot, WriteError},
     },
     utils::{LoadFromPath, StrongholdFlags, VaultFlags},
-    Location,
+    Location, RecordInfo,
+};
+use engine::{
+    snapshot::{kdf::Argon2Params, SnapshotHeader, SnapshotKey},
+    vault::{ClientId, RecordHint, RecordId},
 };
-use engine::vault::{ClientId, RecordHint, RecordId};
 
 use actix::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use thiserror::Error as DeriveError;
 use zeroize::Zeroize;
 
@@ -44,7 +55,6 @@ use crate::{
         network_messages::SwarmInfo,
         GetNetwork, InsertNetwork, RemoveNetwork,
     },
-    procedures::FatalProcedureError,
     state::p2p::{FirewallChannelSender, Network, NetworkConfig, Permissions, WriteToRemoteVault},
 };
 #[cfg(feature = "p2p")]
@@ -143,6 +153,15 @@ impl From<RecordError> for FatalEngineError {
     }
 }
 
+impl From<ProcedureError> for FatalEngineError {
+    fn from(e: ProcedureError) -> Self {
+        match e {
+            ProcedureError::Engine(e) => e,
+            e => FatalEngineError(e.to_string()),
+        }
+    }
+}
+
 impl From<String> for FatalEngineError {
     fn from(e: String) -> Self {
         FatalEngineError(e)
@@ -194,13 +213,14 @@ impl Stronghold {
 
     /// Writes data into the Stronghold. Uses the current target actor as the client and writes to the specified
     /// location of [`Location`] type. The payload must be specified as a [`Vec<u8>`] and a [`RecordHint`] can be
-    /// provided. Also accepts [`VaultFlags`] for when a new Vault is created.
+    /// provided. Also accepts [`VaultFlags`] for when a new Vault is created, they are ignored if the vault already
+    /// exists. Writes that violate the policy of the vault fail.
     pub async fn write_to_vault(
         &self,
         location: Location,
         payload: Vec<u8>,
         hint: RecordHint,
-        _options: Vec<VaultFlags>,
+        options: Vec<VaultFlags>,
     ) -> StrongholdResult<Result<(), FatalEngineError>> {
         let target = self.target().await?;
         // write to vault
@@ -209,6 +229,7 @@ impl Stronghold {
                 location,
                 payload,
                 hint,
+                options,
             })
             .await?
             .map_err(FatalEngineError::from);
@@ -293,11 +314,37 @@ impl Stronghold {
         Ok(vault_exists)
     }
 
+    /// Rotates the key of the vault at `vault_path` in the current target actor. A fresh key is generated and all
+    /// records are re-sealed under it, keeping their [`RecordId`] and [`RecordHint`]. Revoked records are garbage
+    /// collected. If an error occurs the vault remains unchanged and usable with its former key.
+    ///
+    /// Return `false` if the vault does not exist.
+    pub async fn rotate_vault_key<V: Into<Vec<u8>>>(
+        &self,
+        vault_path: V,
+    ) -> StrongholdResult<Result<bool, FatalEngineError>> {
+        let target = self.target().await?;
+        let res = target
+            .send(RotateVaultKey {
+                vault_path: vault_path.into(),
+            })
+            .await?
+            .map_err(FatalEngineError::from);
+        Ok(res)
+    }
+
     /// Returns a list of the available [`RecordId`] and [`RecordHint`] values in a vault by the given `vault_path`.
     pub async fn list_hints_and_ids<V: Into<Vec<u8>>>(
         &self,
         vault_path: V,
     ) -> StrongholdResult<Vec<(RecordId, RecordHint)>> {
+        let list = self.list_records(vault_path).await?;
+        Ok(list.into_iter().map(|info| (info.id, info.hint)).collect())
+    }
+
+    /// Returns a list of the records in a vault by the given `vault_path`, together with their
+    /// [`RecordMetadata`][crate::RecordMetadata].
+    pub async fn list_records<V: Into<Vec<u8>>>(&self, vault_path: V) -> StrongholdResult<Vec<RecordInfo>> {
         let target = self.target().await?;
         let list = target
             .send(ListIds {
@@ -312,8 +359,26 @@ impl Stronghold {
     where
         P: Procedure + Into<StrongholdProcedure>,
     {
-        let res = self.runtime_exec_chained(vec![procedure.into()]).await?;
-        let mapped = res.map(|mut vec| vec.pop().unwrap().try_into().ok().unwrap());
+        self.runtime_exec_with_options(procedure, ExecutionOptions::default())
+            .await
+    }
+
+    /// Executes a single [`StrongholdProcedure`] with a timeout and / or a
+    /// [`CancellationToken`][crate::procedures::CancellationToken], as specified by the [`ExecutionOptions`].
+    pub async fn runtime_exec_with_options<P>(
+        &self,
+        procedure: P,
+        options: ExecutionOptions,
+    ) -> StrongholdResult<Result<P::Output, ProcedureError>>
+    where
+        P: Procedure + Into<StrongholdProcedure>,
+    {
+        let res = self
+            .runtime_exec_pipeline_with_options(vec![procedure.into()], Vec::new(), options)
+            .await?;
+        let mapped = res
+            .map_err(ProcedureError::into_inner)
+            .and_then(|mut vec| typed_output(vec.pop()));
         Ok(mapped)
     }
 
@@ -321,13 +386,54 @@ impl Stronghold {
     pub async fn runtime_exec_chained(
         &self,
         procedures: Vec<StrongholdProcedure>,
+    ) -> StrongholdResult<Result<Vec<ProcedureOutput>, ProcedureError>> {
+        self.runtime_exec_pipeline(procedures, Vec::new()).await
+    }
+
+    /// Sequentially execute multiple [`StrongholdProcedure`]s, where each [`ChainInput`] passes the output of a
+    /// procedure to an input of a later one.  Intermediate secrets can be written to [`Location::ephemeral`]
+    /// locations, which are removed once the chain has finished.
+    pub async fn runtime_exec_pipeline(
+        &self,
+        procedures: Vec<StrongholdProcedure>,
+        inputs: Vec<ChainInput>,
+    ) -> StrongholdResult<Result<Vec<ProcedureOutput>, ProcedureError>> {
+        self.runtime_exec_pipeline_with_options(procedures, inputs, ExecutionOptions::default())
+            .await
+    }
+
+    /// Same as [`Stronghold::runtime_exec_pipeline`], with a timeout and / or a
+    /// [`CancellationToken`][crate::procedures::CancellationToken], as specified by the [`ExecutionOptions`].
+    ///
+    /// Procedures are executed in a blocking thread pool on a copy of the vaults that they access, while the client
+    /// keeps handling other messages.  The changes of the chain are applied once it has finished, unless a vault that
+    /// it changed was also changed in the meantime, in which case [`ProcedureError::Conflict`] is returned.  If the
+    /// chain is cancelled or times out none of its changes are applied either.
+    pub async fn runtime_exec_pipeline_with_options(
+        &self,
+        procedures: Vec<StrongholdProcedure>,
+        inputs: Vec<ChainInput>,
+        options: ExecutionOptions,
     ) -> StrongholdResult<Result<Vec<ProcedureOutput>, ProcedureError>> {
         let target = self.target().await?;
-        let message = Procedures { procedures };
+        let message = Procedures {
+            procedures,
+            inputs,
+            timeout: options.timeout,
+            cancellation: options.cancellation,
+        };
         let result = target.send(message).await?;
         Ok(result)
     }
 
+    /// Registers a user-defined procedure for all clients of this Stronghold.  It can then be executed with a
+    /// [`CustomProcedure`][crate::procedures::CustomProcedure] of the same name.  A procedure that was registered
+    /// before under that name is replaced.
+    pub async fn register_procedure(&self, registration: ProcedureRegistration) -> StrongholdResult<()> {
+        self.registry.send(RegisterProcedure { registration }).await?;
+        Ok(())
+    }
+
     /// Checks whether a record exists in the client based off of the given [`Location`].
     pub async fn record_exists(&self, location: Location) -> StrongholdResult<bool> {
         let target = self.target().await?;
@@ -335,6 +441,22 @@ impl Stronghold {
         Ok(exists)
     }
 
+    /// Returns the entries of the audit log of the current client that match the [`AuditQuery`].  Use
+    /// [`AuditQuery::default`] to read the whole log.
+    pub async fn read_audit_log(&self, query: AuditQuery) -> StrongholdResult<Vec<AuditEntry>> {
+        let target = self.target().await?;
+        let entries = target.send(ReadAuditLog { query }).await?;
+        Ok(entries)
+    }
+
+    /// Verifies the hash chain of the audit log of the current client.  Returns the hash of the latest entry, which
+    /// may be stored outside of Stronghold to detect later modifications of the log.
+    pub async fn verify_audit_log(&self) -> StrongholdResult<Result<[u8; AUDIT_HASH_LEN], AuditError>> {
+        let target = self.target().await?;
+        let res = target.send(VerifyAuditLog).await?;
+        Ok(res)
+    }
+
     /// checks whether a vault exists in the client.
     pub async fn vault_exists<V: Into<Vec<u8>>>(&self, vault_path: V) -> StrongholdResult<bool> {
         let target = self.target().await?;
@@ -357,6 +479,40 @@ impl Stronghold {
         keydata: &T,
         filename: Option<String>,
         path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), ReadError>> {
+        let mut key: [u8; 32] = [0u8; 32];
+        let keydata = keydata.as_ref();
+
+        key.copy_from_slice(keydata);
+
+        self.read_snapshot_with_key(client_path, former_client_path, SnapshotKey::Key(key), filename, path)
+            .await
+    }
+
+    /// Reads data from a given snapshot file that was written with
+    /// [`Stronghold::write_all_to_snapshot_with_password`]. The snapshot key is derived from the password with the
+    /// salt and Argon2id parameters stored in the snapshot header. See [`Stronghold::read_snapshot`] for the remaining
+    /// arguments.
+    pub async fn read_snapshot_with_password<T: Zeroize + AsRef<Vec<u8>>>(
+        &mut self,
+        client_path: Vec<u8>,
+        former_client_path: Option<Vec<u8>>,
+        password: &T,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), ReadError>> {
+        let key = SnapshotKey::Password(password.as_ref().clone().into(), Argon2Params::default());
+        self.read_snapshot_with_key(client_path, former_client_path, key, filename, path)
+            .await
+    }
+
+    async fn read_snapshot_with_key(
+        &mut self,
+        client_path: Vec<u8>,
+        former_client_path: Option<Vec<u8>>,
+        key: SnapshotKey,
+        filename: Option<String>,
+        path: Option<PathBuf>,
     ) -> StrongholdResult<Result<(), ReadError>> {
         let client_id = ClientId::load_from_path(&client_path, &client_path);
         let former_client_id = former_client_path.map(|cp| ClientId::load_from_path(&cp, &cp));
@@ -370,11 +526,6 @@ impl Stronghold {
             self.target().await?
         };
 
-        let mut key: [u8; 32] = [0u8; 32];
-        let keydata = keydata.as_ref();
-
-        key.copy_from_slice(keydata);
-
         // get address of snapshot actor
         let snapshot_actor = self.registry.send(GetSnapshot {}).await?;
 
@@ -412,14 +563,39 @@ impl Stronghold {
         filename: Option<String>,
         path: Option<PathBuf>,
     ) -> StrongholdResult<Result<(), WriteError>> {
-        // this should be delegated to the secure client actor
-        // wrapping the interior functionality inside it.
-        let clients: Vec<(ClientId, Addr<SecureClient>)> = self.registry.send(GetAllClients).await?;
-
         let mut key: [u8; 32] = [0u8; 32];
         let keydata = keydata.as_ref();
         key.copy_from_slice(keydata);
 
+        self.write_all_to_snapshot_with_key(SnapshotKey::Key(key), filename, path)
+            .await
+    }
+
+    /// Writes the entire state of the [`Stronghold`] into a snapshot, that is encrypted with a key derived from the
+    /// password with Argon2id. A fresh salt is generated on each write, and it is stored in the snapshot header together
+    /// with the `params`, so that the snapshot can later be read with only the password. If no `params` are given, the
+    /// [default](Argon2Params::default) costs are used.
+    pub async fn write_all_to_snapshot_with_password<T: Zeroize + AsRef<Vec<u8>>>(
+        &mut self,
+        password: &T,
+        params: Option<Argon2Params>,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), WriteError>> {
+        let key = SnapshotKey::Password(password.as_ref().clone().into(), params.unwrap_or_default());
+        self.write_all_to_snapshot_with_key(key, filename, path).await
+    }
+
+    async fn write_all_to_snapshot_with_key(
+        &mut self,
+        key: SnapshotKey,
+        filename: Option<String>,
+        path: Option<PathBuf>,
+    ) -> StrongholdResult<Result<(), WriteError>> {
+        // this should be delegated to the secure client actor
+        // wrapping the interior functionality inside it.
+        let clients: Vec<(ClientId, Addr<SecureClient>)> = self.registry.send(GetAllClients).await?;
+
         // get snapshot actor
         let snapshot = self.registry.send(GetSnapshot {}).await?;
 
@@ -436,6 +612,30 @@ impl Stronghold {
         Ok(res)
     }
 
+    /// Changes the key of the snapshot file at `path` from `old_key` to `new_key`, without spawning any clients. The
+    /// state of every client contained in the snapshot is loaded and atomically written back under the new key. Either
+    /// key may be a raw key or a password, so this can also be used to migrate a snapshot to a password derived key.
+    pub fn rekey_snapshot(path: &Path, old_key: &SnapshotKey, new_key: &SnapshotKey) -> Result<(), RekeyError> {
+        Snapshot::rekey(path, old_key, new_key)
+    }
+
+    /// Sets the label that is recorded in the header of snapshots written afterwards. Reading a snapshot replaces the
+    /// label with the one of the read snapshot.
+    pub async fn set_snapshot_label(&self, label: Option<String>) -> StrongholdResult<()> {
+        let snapshot = self.registry.send(GetSnapshot {}).await?;
+        snapshot.send(SetSnapshotLabel { label }).await?;
+        Ok(())
+    }
+
+    /// Reads the header of a snapshot file without decrypting it. The header describes the key derivation, the
+    /// compression, the creation time, the contained clients and the label of the snapshot. Snapshots of the former
+    /// version 2.0 are supported as well, but their header contains no fields.
+    ///
+    /// **Note**: The header is only authenticated once the snapshot is decrypted.
+    pub fn read_snapshot_header(filename: Option<String>, path: Option<PathBuf>) -> Result<SnapshotHeader, ReadError> {
+        Snapshot::read_header(filename.as_deref(), path.as_deref())
+    }
+
     /// Used to kill a stronghold actor or clear the cache of the given actor system based on the client_path. If
     /// `kill_actor` is `true`, the actor will be removed from the system.  Otherwise, the cache of the
     /// current target actor will be cleared.
@@ -459,12 +659,6 @@ impl Stronghold {
         Ok(())
     }
 
-    /// Unimplemented until Policies are implemented.
-    #[allow(dead_code)]
-    fn check_config_flags() {
-        unimplemented!()
-    }
-
     /// A test function for reading data from a vault.
     // API CHANGE!
     #[cfg(test)]
@@ -486,6 +680,13 @@ impl Stronghold {
     }
 }
 
+// Converts the output of a single procedure into its output type. This fails if the output is missing or of another
+// variant, which can only happen for outputs that were returned by a remote peer.
+fn typed_output<T: TryFrom<ProcedureOutput>>(output: Option<ProcedureOutput>) -> Result<T, ProcedureError> {
+    let e = || ProcedureError::from(FatalProcedureError::from("unexpected procedure output".to_string()));
+    output.ok_or_else(e)?.try_into().map_err(|_| e())
+}
+
 #[cfg(feature = "p2p")]
 impl Stronghold {
     /// Spawn the p2p-network actor and swarm.
@@ -731,7 +932,7 @@ impl Stronghold {
         location: Location,
         payload: Vec<u8>,
         hint: RecordHint,
-        _options: Vec<VaultFlags>,
+        options: Vec<VaultFlags>,
     ) -> P2pResult<Result<(), FatalEngineError>> {
         let actor = self.network_actor().await?;
 
@@ -743,6 +944,7 @@ impl Stronghold {
                 location: location.clone(),
                 payload: payload.clone(),
                 hint,
+                options,
             },
         };
         let res = actor.send(send_request).await??.map_err(FatalEngineError::from);
@@ -795,6 +997,18 @@ impl Stronghold {
         client_path: Vec<u8>,
         vault_path: V,
     ) -> P2pResult<Vec<(RecordId, RecordHint)>> {
+        let list = self.list_remote_records(peer, client_path, vault_path).await?;
+        Ok(list.into_iter().map(|info| (info.id, info.hint)).collect())
+    }
+
+    /// Returns a list of the records of a remote vault, together with their
+    /// [`RecordMetadata`][crate::RecordMetadata].
+    pub async fn list_remote_records<V: Into<Vec<u8>>>(
+        &self,
+        peer: PeerId,
+        client_path: Vec<u8>,
+        vault_path: V,
+    ) -> P2pResult<Vec<RecordInfo>> {
         let actor = self.network_actor().await?;
         let send_request = network_messages::SendRequest {
             client_path,
@@ -821,7 +1035,9 @@ impl Stronghold {
         let res = self
             .remote_runtime_exec_chained(peer, client_path, vec![procedure.into()])
             .await?;
-        let mapped = res.map(|mut vec| vec.pop().unwrap().try_into().ok().unwrap());
+        let mapped = res
+            .map_err(ProcedureError::into_inner)
+            .and_then(|mut vec| typed_output(vec.pop()));
         Ok(mapped)
     }
 
@@ -834,7 +1050,12 @@ impl Stronghold {
         procedures: Vec<StrongholdProcedure>,
     ) -> P2pResult<Result<Vec<ProcedureOutput>, ProcedureError>> {
         let actor = self.network_actor().await?;
-        let request = Procedures { procedures };
+        let request = Procedures {
+            procedures,
+            inputs: Vec::new(),
+            timeout: None,
+            cancellation: None,
+        };
         let send_request = network_messages::SendRequest {
             client_path,
             peer,
@@ -39,14 +39,23 @@ mod tests;
 pub use crate::{
     interface::{ActorError, FatalEngineError, Stronghold, StrongholdResult},
     internals::Provider,
-    state::snapshot::{ReadError, WriteError},
-    utils::{Location, StrongholdFlags, VaultFlags},
+    state::{
+        audit::{
+            AuditEntry, AuditError, AuditLog, AuditOperation, AuditOutcome, AuditProcedure, AuditQuery, AUDIT_HASH_LEN,
+        },
+        snapshot::{ReadError, RekeyError, WriteError},
+    },
+    utils::{
+        Location, PolicyError, RecordInfo, RecordMetadata, StrongholdFlags, VaultFlags, VaultPolicy, AUDIT_VAULT_PATH,
+        EPHEMERAL_VAULT_PATH,
+    },
 };
 pub use engine::{
+    runtime::GuardedVec,
     snapshot::{
         files::{home_dir, snapshot_dir},
-        kdf::naive_kdf,
-        Key,
+        kdf::{naive_kdf, Argon2Params},
+        Compression, Key, KeyDerivation, SnapshotHeader, SnapshotInfo, SnapshotKey,
     },
     vault::{RecordHint, RecordId},
 };
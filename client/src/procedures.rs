@@ -1,15 +1,25 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+mod custom;
 mod primitives;
 mod types;
+mod wordlist;
 
+pub(crate) use custom::ProcedureRegistry;
+pub use custom::{CustomProcedure, ProcedureRegistration};
 pub use primitives::{
-    AeadCipher, AeadDecrypt, AeadEncrypt, BIP39Generate, BIP39Recover, Chain, ChainCode, CopyRecord, Ed25519Sign,
-    GarbageCollect, GenerateKey, Hkdf, Hmac, KeyType, MnemonicLanguage, Pbkdf2Hmac, PublicKey, RevokeData, Sha2Hash,
-    Slip10Derive, Slip10DeriveInput, Slip10Generate, StrongholdProcedure, WriteVault, X25519DiffieHellman,
+    AeadCipher, AeadDecrypt, AeadEncrypt, Argon2, Argon2Variant, BIP39Generate, BIP39Recover, BIP39Validate,
+    Bip32Derive, Bip32Xpub, Chain, ChainCode, ChainInput, CopyRecord, Ed25519Sign, Ed25519Verify, GarbageCollect,
+    GenerateKey, Hash, HashInput, HashSecret, HashType, Hkdf, Hmac, Hotp, ImportOtpauth, ImportPkcs8, InputSlot,
+    JwsAlgorithm, JwsSign, JwsVerify, KeyType, MnemonicLanguage, MnemonicLength, OtpAlgorithm, OtpKind, OtpParameters,
+    Pbkdf2Hmac, PublicKey, PublicKeyEncoding, RevokeData, Scrypt, SealedBoxDecrypt, SealedBoxEncrypt,
+    Secp256k1EcdsaSign, Sha2Hash, ShamirCombine, ShamirSplit, ShamirTargets, Slip10Derive, Slip10DeriveInput,
+    Slip10Generate, StrongholdProcedure, Totp, UnwrapKey, WrapAlgorithm, WrapKey, WriteVault, X25519DiffieHellman,
+    BIP32_HARDENED, PASSWORD_HASH_MAX_KEY_LEN, SCRYPT_MAX_LOG_N, SCRYPT_MAX_MEMORY, SCRYPT_MAX_PARALLELISM,
 };
+pub(crate) use types::Runner;
 pub use types::{
-    DeriveSecret, FatalProcedureError, GenerateSecret, Procedure, ProcedureError, ProcedureOutput, UseSecret,
+    CancellationToken, DeriveSecret, ExecutionOptions, FatalProcedureError, GenerateSecret, Procedure, ProcedureError,
+    ProcedureKind, ProcedureOutput, Products, UnexpectedOutput, UseSecret,
 };
-pub(crate) use types::{Products, Runner};
@@ -0,0 +1,180 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! User-defined procedures.
+//!
+//! Procedures that implement [`GenerateSecret`], [`DeriveSecret`] or [`UseSecret`] outside of this crate can be
+//! registered under a unique name with
+//! [`Stronghold::register_procedure`][crate::Stronghold::register_procedure], and are then executed through a
+//! [`CustomProcedure`]. The procedure is serialized into the [`CustomProcedure`] together with the locations that it
+//! accesses, so that it can be sent to remote Strongholds and checked by their firewall like the built-in procedures.
+
+use super::types::*;
+use crate::{state::secure::SecureClient, Location};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use stronghold_utils::GuardDebug;
+
+pub(crate) type CustomExecutor = fn(CustomProcedure, &mut SecureClient) -> Result<ProcedureOutput, ProcedureError>;
+
+/// Registered user-defined procedures by name, shared by all clients of a Stronghold.
+pub(crate) type ProcedureRegistry = Arc<RwLock<HashMap<String, CustomExecutor>>>;
+
+/// Execution of a user-defined procedure that was registered with a [`ProcedureRegistration`].
+#[derive(Clone, GuardDebug, Serialize, Deserialize)]
+pub struct CustomProcedure {
+    /// Name under which the procedure was registered.
+    pub name: String,
+
+    /// Serialized procedure.
+    pub data: Vec<u8>,
+
+    /// Location of the secret that the procedure uses.
+    pub input: Option<Location>,
+
+    /// Location of the secret that the procedure writes.
+    pub output: Option<Location>,
+}
+
+impl CustomProcedure {
+    /// Wraps a procedure that implements [`GenerateSecret`].
+    pub fn generate_secret<P>(name: impl Into<String>, proc: &P) -> Result<Self, FatalProcedureError>
+    where
+        P: GenerateSecret + Serialize,
+    {
+        Self::new(name.into(), proc, None, Some(proc.target().0.clone()))
+    }
+
+    /// Wraps a procedure that implements [`DeriveSecret`].
+    pub fn derive_secret<P>(name: impl Into<String>, proc: &P) -> Result<Self, FatalProcedureError>
+    where
+        P: DeriveSecret + Serialize,
+    {
+        Self::new(
+            name.into(),
+            proc,
+            Some(proc.source().clone()),
+            Some(proc.target().0.clone()),
+        )
+    }
+
+    /// Wraps a procedure that implements [`UseSecret`].
+    pub fn use_secret<P>(name: impl Into<String>, proc: &P) -> Result<Self, FatalProcedureError>
+    where
+        P: UseSecret + Serialize,
+    {
+        Self::new(name.into(), proc, Some(proc.source().clone()), None)
+    }
+
+    fn new<P: Serialize>(
+        name: String,
+        proc: &P,
+        input: Option<Location>,
+        output: Option<Location>,
+    ) -> Result<Self, FatalProcedureError> {
+        let data = bincode::serialize(proc).map_err(|e| e.to_string())?;
+        Ok(CustomProcedure {
+            name,
+            data,
+            input,
+            output,
+        })
+    }
+
+    /// Deserializes the procedure and checks that it accesses the same locations as stated in `input` and
+    /// `output`, which are the ones that the firewall of a remote Stronghold granted access to.
+    fn decode<P: DeserializeOwned>(
+        &self,
+        locations: impl FnOnce(&P) -> (Option<&Location>, Option<&Location>),
+    ) -> Result<P, FatalProcedureError> {
+        let proc: P = bincode::deserialize(&self.data).map_err(|e| e.to_string())?;
+        if locations(&proc) != (self.input.as_ref(), self.output.as_ref()) {
+            let e = format!("locations of procedure {} do not match", self.name);
+            return Err(e.into());
+        }
+        Ok(proc)
+    }
+}
+
+impl Procedure for CustomProcedure {
+    type Output = ProcedureOutput;
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        runner.execute_custom(self)
+    }
+}
+
+/// User-defined procedure that can be registered with
+/// [`Stronghold::register_procedure`][crate::Stronghold::register_procedure].
+pub struct ProcedureRegistration {
+    pub(crate) name: String,
+    pub(crate) executor: CustomExecutor,
+}
+
+impl ProcedureRegistration {
+    /// Registers the [`GenerateSecret`] procedure `P` under `name`.
+    pub fn generate_secret<P>(name: impl Into<String>) -> Self
+    where
+        P: GenerateSecret + DeserializeOwned,
+        P::Output: Into<ProcedureOutput>,
+    {
+        ProcedureRegistration {
+            name: name.into(),
+            executor: execute_generate::<P>,
+        }
+    }
+
+    /// Registers the [`DeriveSecret`] procedure `P` under `name`.
+    pub fn derive_secret<P>(name: impl Into<String>) -> Self
+    where
+        P: DeriveSecret + DeserializeOwned,
+        P::Output: Into<ProcedureOutput>,
+    {
+        ProcedureRegistration {
+            name: name.into(),
+            executor: execute_derive::<P>,
+        }
+    }
+
+    /// Registers the [`UseSecret`] procedure `P` under `name`.
+    pub fn use_secret<P>(name: impl Into<String>) -> Self
+    where
+        P: UseSecret + DeserializeOwned,
+        P::Output: Into<ProcedureOutput>,
+    {
+        ProcedureRegistration {
+            name: name.into(),
+            executor: execute_use::<P>,
+        }
+    }
+}
+
+fn execute_generate<P>(proc: CustomProcedure, runner: &mut SecureClient) -> Result<ProcedureOutput, ProcedureError>
+where
+    P: GenerateSecret + DeserializeOwned,
+    P::Output: Into<ProcedureOutput>,
+{
+    let proc: P = proc.decode(|p: &P| (None, Some(p.target().0)))?;
+    proc.exec(runner).map(Into::into)
+}
+
+fn execute_derive<P>(proc: CustomProcedure, runner: &mut SecureClient) -> Result<ProcedureOutput, ProcedureError>
+where
+    P: DeriveSecret + DeserializeOwned,
+    P::Output: Into<ProcedureOutput>,
+{
+    let proc: P = proc.decode(|p: &P| (Some(p.source()), Some(p.target().0)))?;
+    proc.exec(runner).map(Into::into)
+}
+
+fn execute_use<P>(proc: CustomProcedure, runner: &mut SecureClient) -> Result<ProcedureOutput, ProcedureError>
+where
+    P: UseSecret + DeserializeOwned,
+    P::Output: Into<ProcedureOutput>,
+{
+    let proc: P = proc.decode(|p: &P| (Some(p.source()), None))?;
+    proc.exec(runner).map(Into::into)
+}
@@ -1,16 +1,21 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use super::types::*;
+use super::{custom::CustomProcedure, types::*, wordlist};
 use crate::{state::secure::SecureClient, Location};
 pub use crypto::keys::slip10::{Chain, ChainCode};
 use crypto::{
     ciphers::{
         aes::Aes256Gcm,
+        aes_kw::{Aes128Kw, Aes192Kw, Aes256Kw, BLOCK as AES_KW_BLOCK},
         chacha::XChaCha20Poly1305,
         traits::{Aead, Tag},
     },
-    hashes::sha::{Sha256, Sha384, Sha512, SHA256_LEN, SHA384_LEN, SHA512_LEN},
+    hashes::{
+        blake2b::Blake2b256,
+        sha::{Sha256, Sha384, Sha512, SHA256_LEN, SHA384_LEN, SHA512_LEN},
+        Digest,
+    },
     keys::{
         bip39,
         pbkdf::{PBKDF2_HMAC_SHA256, PBKDF2_HMAC_SHA384, PBKDF2_HMAC_SHA512},
@@ -20,9 +25,16 @@ use crypto::{
     signatures::ed25519,
     utils::rand::fill,
 };
-use engine::{runtime::GuardedVec, vault::RecordHint};
+use engine::{
+    runtime::GuardedVec,
+    snapshot::kdf::Argon2Params,
+    vault::{RecordHint, VaultId},
+};
 use serde::{Deserialize, Serialize};
+use sharks::{Share, Sharks};
+use std::time::Duration;
 use stronghold_utils::GuardDebug;
+use zeroize::Zeroizing;
 
 /// Enum that wraps all cryptographic procedures that are supported by Stronghold.
 ///  
@@ -36,17 +48,39 @@ pub enum StrongholdProcedure {
     CopyRecord(CopyRecord),
     Slip10Generate(Slip10Generate),
     Slip10Derive(Slip10Derive),
+    Bip32Derive(Bip32Derive),
+    Bip32Xpub(Bip32Xpub),
     BIP39Generate(BIP39Generate),
     BIP39Recover(BIP39Recover),
+    BIP39Validate(BIP39Validate),
     PublicKey(PublicKey),
     GenerateKey(GenerateKey),
+    ImportPkcs8(ImportPkcs8),
     Ed25519Sign(Ed25519Sign),
+    Ed25519Verify(Ed25519Verify),
+    Secp256k1EcdsaSign(Secp256k1EcdsaSign),
+    JwsSign(JwsSign),
+    JwsVerify(JwsVerify),
     X25519DiffieHellman(X25519DiffieHellman),
     Hmac(Hmac),
+    Hotp(Hotp),
+    Totp(Totp),
+    ImportOtpauth(ImportOtpauth),
     Hkdf(Hkdf),
     Pbkdf2Hmac(Pbkdf2Hmac),
+    Argon2(Argon2),
+    Scrypt(Scrypt),
     AeadEncrypt(AeadEncrypt),
     AeadDecrypt(AeadDecrypt),
+    WrapKey(WrapKey),
+    UnwrapKey(UnwrapKey),
+    SealedBoxEncrypt(SealedBoxEncrypt),
+    SealedBoxDecrypt(SealedBoxDecrypt),
+    Hash(Hash),
+    HashSecret(HashSecret),
+    ShamirSplit(ShamirSplit),
+    ShamirCombine(ShamirCombine),
+    CustomProcedure(CustomProcedure),
 }
 
 impl Procedure for StrongholdProcedure {
@@ -61,22 +95,90 @@ impl Procedure for StrongholdProcedure {
             CopyRecord(proc) => proc.execute(runner).map(|o| o.into()),
             Slip10Generate(proc) => proc.execute(runner).map(|o| o.into()),
             Slip10Derive(proc) => proc.execute(runner).map(|o| o.into()),
+            Bip32Derive(proc) => proc.execute(runner).map(|o| o.into()),
+            Bip32Xpub(proc) => proc.execute(runner).map(|o| o.into()),
             BIP39Generate(proc) => proc.execute(runner).map(|o| o.into()),
             BIP39Recover(proc) => proc.execute(runner).map(|o| o.into()),
+            BIP39Validate(proc) => proc.execute(runner).map(|o| o.into()),
             GenerateKey(proc) => proc.execute(runner).map(|o| o.into()),
+            ImportPkcs8(proc) => proc.execute(runner).map(|o| o.into()),
             PublicKey(proc) => proc.execute(runner).map(|o| o.into()),
             Ed25519Sign(proc) => proc.execute(runner).map(|o| o.into()),
+            Ed25519Verify(proc) => proc.execute(runner).map(|o| o.into()),
+            Secp256k1EcdsaSign(proc) => proc.execute(runner).map(|o| o.into()),
+            JwsSign(proc) => proc.execute(runner).map(|o| o.into()),
+            JwsVerify(proc) => proc.execute(runner).map(|o| o.into()),
             X25519DiffieHellman(proc) => proc.execute(runner).map(|o| o.into()),
             Hmac(proc) => proc.execute(runner).map(|o| o.into()),
+            Hotp(proc) => proc.execute(runner).map(|o| o.into()),
+            Totp(proc) => proc.execute(runner).map(|o| o.into()),
+            ImportOtpauth(proc) => proc.execute(runner).map(|o| o.into()),
             Hkdf(proc) => proc.execute(runner).map(|o| o.into()),
             Pbkdf2Hmac(proc) => proc.execute(runner).map(|o| o.into()),
+            Argon2(proc) => proc.execute(runner).map(|o| o.into()),
+            Scrypt(proc) => proc.execute(runner).map(|o| o.into()),
             AeadEncrypt(proc) => proc.execute(runner).map(|o| o.into()),
             AeadDecrypt(proc) => proc.execute(runner).map(|o| o.into()),
+            WrapKey(proc) => proc.execute(runner).map(|o| o.into()),
+            UnwrapKey(proc) => proc.execute(runner).map(|o| o.into()),
+            SealedBoxEncrypt(proc) => proc.execute(runner).map(|o| o.into()),
+            SealedBoxDecrypt(proc) => proc.execute(runner).map(|o| o.into()),
+            Hash(proc) => proc.execute(runner).map(|o| o.into()),
+            HashSecret(proc) => proc.execute(runner).map(|o| o.into()),
+            ShamirSplit(proc) => proc.execute(runner).map(|o| o.into()),
+            ShamirCombine(proc) => proc.execute(runner).map(|o| o.into()),
+            CustomProcedure(proc) => proc.execute(runner),
         }
     }
 }
 
 impl StrongholdProcedure {
+    /// Name of the procedure, as it is recorded in the metadata of the records that it creates.
+    pub fn name(&self) -> &'static str {
+        use StrongholdProcedure::*;
+        match self {
+            WriteVault(_) => "WriteVault",
+            RevokeData(_) => "RevokeData",
+            GarbageCollect(_) => "GarbageCollect",
+            CopyRecord(_) => "CopyRecord",
+            Slip10Generate(_) => "Slip10Generate",
+            Slip10Derive(_) => "Slip10Derive",
+            Bip32Derive(_) => "Bip32Derive",
+            Bip32Xpub(_) => "Bip32Xpub",
+            BIP39Generate(_) => "BIP39Generate",
+            BIP39Recover(_) => "BIP39Recover",
+            BIP39Validate(_) => "BIP39Validate",
+            PublicKey(_) => "PublicKey",
+            GenerateKey(_) => "GenerateKey",
+            ImportPkcs8(_) => "ImportPkcs8",
+            Ed25519Sign(_) => "Ed25519Sign",
+            Ed25519Verify(_) => "Ed25519Verify",
+            Secp256k1EcdsaSign(_) => "Secp256k1EcdsaSign",
+            JwsSign(_) => "JwsSign",
+            JwsVerify(_) => "JwsVerify",
+            X25519DiffieHellman(_) => "X25519DiffieHellman",
+            Hmac(_) => "Hmac",
+            Hotp(_) => "Hotp",
+            Totp(_) => "Totp",
+            ImportOtpauth(_) => "ImportOtpauth",
+            Hkdf(_) => "Hkdf",
+            Pbkdf2Hmac(_) => "Pbkdf2Hmac",
+            Argon2(_) => "Argon2",
+            Scrypt(_) => "Scrypt",
+            AeadEncrypt(_) => "AeadEncrypt",
+            AeadDecrypt(_) => "AeadDecrypt",
+            WrapKey(_) => "WrapKey",
+            UnwrapKey(_) => "UnwrapKey",
+            SealedBoxEncrypt(_) => "SealedBoxEncrypt",
+            SealedBoxDecrypt(_) => "SealedBoxDecrypt",
+            Hash(_) => "Hash",
+            HashSecret(_) => "HashSecret",
+            ShamirSplit(_) => "ShamirSplit",
+            ShamirCombine(_) => "ShamirCombine",
+            CustomProcedure(_) => "CustomProcedure",
+        }
+    }
+
     pub(crate) fn input(&self) -> Option<Location> {
         match self {
             StrongholdProcedure::CopyRecord(CopyRecord { source: input, .. })
@@ -88,13 +190,38 @@ impl StrongholdProcedure {
                 input: Slip10DeriveInput::Key(input),
                 ..
             })
+            | StrongholdProcedure::Bip32Derive(Bip32Derive {
+                input: Slip10DeriveInput::Seed(input),
+                ..
+            })
+            | StrongholdProcedure::Bip32Derive(Bip32Derive {
+                input: Slip10DeriveInput::Key(input),
+                ..
+            })
+            | StrongholdProcedure::Bip32Xpub(Bip32Xpub { private_key: input })
             | StrongholdProcedure::PublicKey(PublicKey { private_key: input, .. })
             | StrongholdProcedure::Ed25519Sign(Ed25519Sign { private_key: input, .. })
+            | StrongholdProcedure::Secp256k1EcdsaSign(Secp256k1EcdsaSign { private_key: input, .. })
+            | StrongholdProcedure::JwsSign(JwsSign { private_key: input, .. })
             | StrongholdProcedure::X25519DiffieHellman(X25519DiffieHellman { private_key: input, .. })
             | StrongholdProcedure::Hkdf(Hkdf { ikm: input, .. })
             | StrongholdProcedure::Hmac(Hmac { key: input, .. })
+            | StrongholdProcedure::Hotp(Hotp { key: input, .. })
+            | StrongholdProcedure::Totp(Totp { key: input, .. })
             | StrongholdProcedure::AeadEncrypt(AeadEncrypt { key: input, .. })
-            | StrongholdProcedure::AeadDecrypt(AeadDecrypt { key: input, .. }) => Some(input.clone()),
+            | StrongholdProcedure::AeadDecrypt(AeadDecrypt { key: input, .. })
+            | StrongholdProcedure::WrapKey(WrapKey { key: input, .. })
+            | StrongholdProcedure::UnwrapKey(UnwrapKey {
+                wrapping_key: input, ..
+            })
+            | StrongholdProcedure::SealedBoxDecrypt(SealedBoxDecrypt { private_key: input, .. })
+            | StrongholdProcedure::Hash(Hash {
+                input: HashInput::Secret(input),
+                ..
+            })
+            | StrongholdProcedure::HashSecret(HashSecret { input, .. })
+            | StrongholdProcedure::ShamirSplit(ShamirSplit { secret: input, .. }) => Some(input.clone()),
+            StrongholdProcedure::CustomProcedure(CustomProcedure { input, .. }) => input.clone(),
             _ => None,
         }
     }
@@ -104,15 +231,133 @@ impl StrongholdProcedure {
             | StrongholdProcedure::CopyRecord(CopyRecord { target: output, .. })
             | StrongholdProcedure::Slip10Generate(Slip10Generate { output, .. })
             | StrongholdProcedure::Slip10Derive(Slip10Derive { output, .. })
+            | StrongholdProcedure::Bip32Derive(Bip32Derive { output, .. })
             | StrongholdProcedure::BIP39Generate(BIP39Generate { output, .. })
             | StrongholdProcedure::BIP39Recover(BIP39Recover { output, .. })
             | StrongholdProcedure::GenerateKey(GenerateKey { output, .. })
+            | StrongholdProcedure::ImportPkcs8(ImportPkcs8 { output, .. })
+            | StrongholdProcedure::ImportOtpauth(ImportOtpauth { output, .. })
             | StrongholdProcedure::X25519DiffieHellman(X25519DiffieHellman { shared_key: output, .. })
             | StrongholdProcedure::Hkdf(Hkdf { okm: output, .. })
-            | StrongholdProcedure::Pbkdf2Hmac(Pbkdf2Hmac { output, .. }) => Some(output.clone()),
+            | StrongholdProcedure::Pbkdf2Hmac(Pbkdf2Hmac { output, .. })
+            | StrongholdProcedure::Argon2(Argon2 { output, .. })
+            | StrongholdProcedure::Scrypt(Scrypt { output, .. })
+            | StrongholdProcedure::HashSecret(HashSecret { output, .. })
+            | StrongholdProcedure::UnwrapKey(UnwrapKey { output, .. })
+            | StrongholdProcedure::SealedBoxDecrypt(SealedBoxDecrypt {
+                output: Some((output, _)),
+                ..
+            })
+            | StrongholdProcedure::ShamirCombine(ShamirCombine { output, .. }) => Some(output.clone()),
+            StrongholdProcedure::CustomProcedure(CustomProcedure { output, .. }) => output.clone(),
             _ => None,
         }
     }
+
+    /// Vaults that the procedure reads records from or writes records to.
+    pub(crate) fn vault_ids(&self) -> Vec<VaultId> {
+        let mut locations: Vec<Location> = self.input().into_iter().chain(self.output()).collect();
+        match self {
+            StrongholdProcedure::RevokeData(RevokeData { location, .. }) => locations.push(location.clone()),
+            StrongholdProcedure::WrapKey(WrapKey { wrapping_key, .. }) => locations.push(wrapping_key.clone()),
+            StrongholdProcedure::ShamirCombine(ShamirCombine { shares, .. }) => {
+                locations.extend(shares.iter().cloned())
+            }
+            StrongholdProcedure::ShamirSplit(ShamirSplit {
+                targets: ShamirTargets::Vault(targets),
+                ..
+            }) => locations.extend(targets.iter().map(|(location, _)| location.clone())),
+            StrongholdProcedure::GarbageCollect(GarbageCollect { vault_path }) => {
+                return vec![SecureClient::derive_vault_id(vault_path)];
+            }
+            _ => {}
+        }
+        locations
+            .iter()
+            .map(|location| SecureClient::resolve_location(location).0)
+            .collect()
+    }
+
+    /// Sets the input `slot` of the procedure to the output of a previous procedure of the chain.
+    pub(crate) fn set_input(&mut self, slot: InputSlot, data: Vec<u8>) -> Result<(), FatalProcedureError> {
+        use StrongholdProcedure::*;
+        let utf8 = |data: Vec<u8>| String::from_utf8(data).map_err(|e| FatalProcedureError::from(e.to_string()));
+        match (self, slot) {
+            (WriteVault(proc), InputSlot::Data) => proc.data = data,
+            (Ed25519Sign(proc), InputSlot::Message) => proc.msg = data,
+            (Ed25519Verify(proc), InputSlot::Message) => proc.msg = data,
+            (Secp256k1EcdsaSign(proc), InputSlot::Message) => proc.msg = input_array(data)?,
+            (JwsSign(proc), InputSlot::Message) => proc.claims = utf8(data)?,
+            (Hmac(proc), InputSlot::Message) => proc.msg = data,
+            (Hash(proc), InputSlot::Message) => proc.input = HashInput::Message(data),
+            (Ed25519Verify(proc), InputSlot::PublicKey) => proc.public_key = data,
+            (JwsVerify(proc), InputSlot::PublicKey) => proc.public_key = data,
+            (X25519DiffieHellman(proc), InputSlot::PublicKey) => proc.public_key = input_array(data)?,
+            (SealedBoxEncrypt(proc), InputSlot::PublicKey) => proc.public_key = input_array(data)?,
+            (Ed25519Verify(proc), InputSlot::Signature) => proc.signature = data,
+            (JwsVerify(proc), InputSlot::Signature) => proc.jws = utf8(data)?,
+            (AeadEncrypt(proc), InputSlot::Plaintext) => proc.plaintext = data,
+            (SealedBoxEncrypt(proc), InputSlot::Plaintext) => proc.plaintext = data,
+            (AeadDecrypt(proc), InputSlot::Ciphertext) => {
+                // The output of `AeadEncrypt` is the tag followed by the ciphertext.
+                let tag_len = match proc.cipher {
+                    AeadCipher::Aes256Gcm => Aes256Gcm::TAG_LENGTH,
+                    AeadCipher::XChaCha20Poly1305 => XChaCha20Poly1305::TAG_LENGTH,
+                };
+                if data.len() < tag_len {
+                    return Err("invalid input length".to_string().into());
+                }
+                proc.tag = data[..tag_len].to_vec();
+                proc.ciphertext = data[tag_len..].to_vec();
+            }
+            (SealedBoxDecrypt(proc), InputSlot::Ciphertext) => proc.ciphertext = data,
+            (UnwrapKey(proc), InputSlot::Ciphertext) => proc.wrapped_key = data,
+            (AeadEncrypt(proc), InputSlot::AssociatedData) => proc.associated_data = data,
+            (AeadDecrypt(proc), InputSlot::AssociatedData) => proc.associated_data = data,
+            (SealedBoxEncrypt(proc), InputSlot::AssociatedData) => proc.associated_data = data,
+            (SealedBoxDecrypt(proc), InputSlot::AssociatedData) => proc.associated_data = data,
+            (proc, slot) => return Err(format!("{} has no input {:?}", proc.name(), slot).into()),
+        }
+        Ok(())
+    }
+}
+
+fn input_array<const N: usize>(data: Vec<u8>) -> Result<[u8; N], FatalProcedureError> {
+    data.try_into()
+        .map_err(|_| FatalProcedureError::from("invalid input length".to_string()))
+}
+
+/// Input of a procedure that can be set to the output of a previous procedure of the same chain with a
+/// [`ChainInput`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputSlot {
+    /// `data` of [`WriteVault`].
+    Data,
+    /// Message of [`Ed25519Sign`], [`Ed25519Verify`], [`Secp256k1EcdsaSign`], [`Hmac`], [`Hash`] and the claims of
+    /// [`JwsSign`].
+    Message,
+    /// Public key of [`Ed25519Verify`], [`JwsVerify`], [`X25519DiffieHellman`] and [`SealedBoxEncrypt`].
+    PublicKey,
+    /// Signature of [`Ed25519Verify`] and the JWS of [`JwsVerify`].
+    Signature,
+    /// Plaintext of [`AeadEncrypt`] and [`SealedBoxEncrypt`].
+    Plaintext,
+    /// Ciphertext of [`AeadDecrypt`] (tag and ciphertext, as returned by [`AeadEncrypt`]), [`SealedBoxDecrypt`] and
+    /// the wrapped key of [`UnwrapKey`].
+    Ciphertext,
+    /// Associated data of [`AeadEncrypt`], [`AeadDecrypt`], [`SealedBoxEncrypt`] and [`SealedBoxDecrypt`].
+    AssociatedData,
+}
+
+/// Passes the output of the procedure at index `source` of a chain to the input `slot` of the later procedure at
+/// index `target`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainInput {
+    pub source: usize,
+
+    pub target: usize,
+
+    pub slot: InputSlot,
 }
 
 /// Implement StrongholdProcedure: From<T> for all.
@@ -150,13 +395,21 @@ macro_rules! procedures {
 
 procedures! {
     // Stronghold procedures that implement the `GenerateSecret` trait.
-    GenerateSecret => { WriteVault, BIP39Generate, BIP39Recover, Slip10Generate, GenerateKey, Pbkdf2Hmac },
+    GenerateSecret => {
+        WriteVault, BIP39Generate, BIP39Recover, Slip10Generate, GenerateKey, ImportPkcs8, ImportOtpauth, Pbkdf2Hmac,
+        Argon2, Scrypt
+    },
     // Stronghold procedures that implement the `DeriveSecret` trait.
-    DeriveSecret => { CopyRecord, Slip10Derive, X25519DiffieHellman, Hkdf },
+    DeriveSecret => { CopyRecord, Slip10Derive, Bip32Derive, X25519DiffieHellman, Hkdf, HashSecret, UnwrapKey },
     // Stronghold procedures that implement the `UseSecret` trait.
-    UseSecret => { PublicKey, Ed25519Sign, Hmac, AeadEncrypt, AeadDecrypt },
+    UseSecret => {
+        PublicKey, Bip32Xpub, Ed25519Sign, Secp256k1EcdsaSign, JwsSign, Hmac, Hotp, Totp, AeadEncrypt, AeadDecrypt
+    },
     // Stronghold procedures that directly implement the `Procedure` trait.
-    _ => { RevokeData, GarbageCollect }
+    _ => {
+        RevokeData, GarbageCollect, BIP39Validate, JwsVerify, ShamirSplit, ShamirCombine, Hash, Ed25519Verify,
+        SealedBoxEncrypt, SealedBoxDecrypt, WrapKey, CustomProcedure
+    }
 }
 
 /// Write data to the specified [`Location`].
@@ -167,6 +420,8 @@ pub struct WriteVault {
     pub location: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl GenerateSecret for WriteVault {
@@ -182,6 +437,10 @@ impl GenerateSecret for WriteVault {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.location, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
 }
 
 /// Revoke the data from the specified [`Location`]. Revoked data is not readable and can be
@@ -231,6 +490,8 @@ pub struct CopyRecord {
     pub target: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl DeriveSecret for CopyRecord {
@@ -251,12 +512,72 @@ impl DeriveSecret for CopyRecord {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.target, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+
+    fn kind(&self) -> ProcedureKind {
+        ProcedureKind::Copy
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum MnemonicLanguage {
     English,
     Japanese,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+impl MnemonicLanguage {
+    fn wordlist(&self) -> bip39::wordlist::Wordlist<'static> {
+        match self {
+            MnemonicLanguage::English => bip39::wordlist::ENGLISH,
+            MnemonicLanguage::Japanese => bip39::wordlist::JAPANESE,
+            MnemonicLanguage::ChineseSimplified => wordlist::CHINESE_SIMPLIFIED,
+            MnemonicLanguage::ChineseTraditional => wordlist::CHINESE_TRADITIONAL,
+            MnemonicLanguage::Czech => wordlist::CZECH,
+            MnemonicLanguage::French => wordlist::FRENCH,
+            MnemonicLanguage::Italian => wordlist::ITALIAN,
+            MnemonicLanguage::Korean => wordlist::KOREAN,
+            MnemonicLanguage::Portuguese => wordlist::PORTUGUESE,
+            MnemonicLanguage::Spanish => wordlist::SPANISH,
+        }
+    }
+}
+
+/// Number of words of a BIP39 mnemonic sentence, which determines the size of the entropy that is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MnemonicLength {
+    /// 128 bit entropy.
+    Words12,
+    /// 160 bit entropy.
+    Words15,
+    /// 192 bit entropy.
+    Words18,
+    /// 224 bit entropy.
+    Words21,
+    /// 256 bit entropy.
+    Words24,
+}
+
+impl MnemonicLength {
+    fn entropy_len(&self) -> usize {
+        match self {
+            MnemonicLength::Words12 => 16,
+            MnemonicLength::Words15 => 20,
+            MnemonicLength::Words18 => 24,
+            MnemonicLength::Words21 => 28,
+            MnemonicLength::Words24 => 32,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -269,6 +590,7 @@ pub enum AeadCipher {
 pub enum KeyType {
     Ed25519,
     X25519,
+    Secp256k1,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -278,6 +600,32 @@ pub enum Sha2Hash {
     Sha512,
 }
 
+/// Hash function that is used by the [`Hash`] and [`HashSecret`] procedures.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum HashType {
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha3_256,
+    Sha3_384,
+    Sha3_512,
+    Blake2b256,
+}
+
+impl HashType {
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashType::Sha256 => Sha256::digest(data).to_vec(),
+            HashType::Sha384 => Sha384::digest(data).to_vec(),
+            HashType::Sha512 => Sha512::digest(data).to_vec(),
+            HashType::Sha3_256 => sha3::Sha3_256::digest(data).to_vec(),
+            HashType::Sha3_384 => sha3::Sha3_384::digest(data).to_vec(),
+            HashType::Sha3_512 => sha3::Sha3_512::digest(data).to_vec(),
+            HashType::Blake2b256 => Blake2b256::digest(data).to_vec(),
+        }
+    }
+}
+
 /// Generate a BIP39 seed and its corresponding mnemonic sentence (optionally protected by a
 /// passphrase). Store the seed and return the mnemonic sentence as data output.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -286,24 +634,23 @@ pub struct BIP39Generate {
 
     pub language: MnemonicLanguage,
 
+    pub length: MnemonicLength,
+
     pub output: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl GenerateSecret for BIP39Generate {
     type Output = String;
 
     fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
-        let mut entropy = [0u8; 32];
+        let mut entropy = Zeroizing::new(vec![0u8; self.length.entropy_len()]);
         fill(&mut entropy)?;
 
-        let wordlist = match self.language {
-            MnemonicLanguage::English => bip39::wordlist::ENGLISH,
-            MnemonicLanguage::Japanese => bip39::wordlist::JAPANESE,
-        };
-
-        let mnemonic = bip39::wordlist::encode(&entropy, &wordlist).unwrap();
+        let mnemonic = bip39::wordlist::encode(&entropy, &self.language.wordlist()).unwrap();
 
         let mut seed = [0u8; 64];
         let passphrase = self.passphrase.unwrap_or_else(|| "".into());
@@ -318,6 +665,10 @@ impl GenerateSecret for BIP39Generate {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.output, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
 }
 
 /// Use a BIP39 mnemonic sentence (optionally protected by a passphrase) to create or recover
@@ -331,6 +682,8 @@ pub struct BIP39Recover {
     pub output: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl GenerateSecret for BIP39Recover {
@@ -349,6 +702,28 @@ impl GenerateSecret for BIP39Recover {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.output, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Check that a BIP39 mnemonic sentence only consists of words of the wordlist of the given language and that its
+/// checksum is valid. This procedure does not access the vault.
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct BIP39Validate {
+    pub mnemonic: String,
+
+    pub language: MnemonicLanguage,
+}
+
+impl Procedure for BIP39Validate {
+    type Output = bool;
+
+    fn execute<R: Runner>(self, _runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let entropy = bip39::wordlist::decode(&self.mnemonic, &self.language.wordlist());
+        Ok(entropy.is_ok())
+    }
 }
 
 /// Generate a raw SLIP10 seed of the specified size (in bytes, defaults to 64 bytes/512 bits) and store it in
@@ -363,6 +738,8 @@ pub struct Slip10Generate {
     pub output: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl GenerateSecret for Slip10Generate {
@@ -381,6 +758,10 @@ impl GenerateSecret for Slip10Generate {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.output, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
 }
 
 #[derive(GuardDebug, Clone, Serialize, Deserialize)]
@@ -401,6 +782,8 @@ pub struct Slip10Derive {
     pub output: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl DeriveSecret for Slip10Derive {
@@ -431,6 +814,220 @@ impl DeriveSecret for Slip10Derive {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.output, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Offset of hardened child indices in a [`Bip32Derive`] chain, as defined in BIP-32.
+pub const BIP32_HARDENED: u32 = 0x8000_0000;
+
+/// Version bytes of a mainnet extended public key (`xpub`).
+const BIP32_XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+
+/// Length of an extended key record: secret key (32) || chain code (32) || depth (1) ||
+/// parent fingerprint (4) || child number (4).
+const BIP32_KEY_LEN: usize = 73;
+
+/// Extended secp256k1 private key as described in BIP-32.
+struct Bip32Key {
+    secret_key: libsecp256k1::SecretKey,
+    chain_code: ChainCode,
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+}
+
+impl Bip32Key {
+    fn from_seed(seed: &[u8]) -> Result<Self, FatalProcedureError> {
+        let mut i = [0u8; SHA512_LEN];
+        HMAC_SHA512(seed, b"Bitcoin seed", &mut i);
+        Self::from_hmac_output(i, 0, [0; 4], 0)
+    }
+
+    /// Parse a stored extended key. Records that only contain the secret key and chain code
+    /// are treated as master keys.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, FatalProcedureError> {
+        if bytes.len() != BIP32_KEY_LEN && bytes.len() != 64 {
+            let e = crypto::Error::BufferSize {
+                has: bytes.len(),
+                needs: BIP32_KEY_LEN,
+                name: "data buffer",
+            };
+            return Err(e.into());
+        }
+        let secret_key =
+            libsecp256k1::SecretKey::parse_slice(&bytes[..32]).map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&bytes[32..64]);
+        let (depth, parent_fingerprint, child_number) = if bytes.len() == BIP32_KEY_LEN {
+            let mut parent_fingerprint = [0u8; 4];
+            parent_fingerprint.copy_from_slice(&bytes[65..69]);
+            let mut child_number = [0u8; 4];
+            child_number.copy_from_slice(&bytes[69..73]);
+            (bytes[64], parent_fingerprint, u32::from_be_bytes(child_number))
+        } else {
+            (0, [0; 4], 0)
+        };
+        Ok(Bip32Key {
+            secret_key,
+            chain_code,
+            depth,
+            parent_fingerprint,
+            child_number,
+        })
+    }
+
+    fn from_hmac_output(
+        i: [u8; SHA512_LEN],
+        depth: u8,
+        parent_fingerprint: [u8; 4],
+        child_number: u32,
+    ) -> Result<Self, FatalProcedureError> {
+        let secret_key =
+            libsecp256k1::SecretKey::parse_slice(&i[..32]).map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&i[32..]);
+        Ok(Bip32Key {
+            secret_key,
+            chain_code,
+            depth,
+            parent_fingerprint,
+            child_number,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BIP32_KEY_LEN);
+        bytes.extend_from_slice(&self.secret_key.serialize());
+        bytes.extend_from_slice(&self.chain_code);
+        bytes.push(self.depth);
+        bytes.extend_from_slice(&self.parent_fingerprint);
+        bytes.extend_from_slice(&self.child_number.to_be_bytes());
+        bytes
+    }
+
+    fn public_key(&self) -> [u8; libsecp256k1::util::COMPRESSED_PUBLIC_KEY_SIZE] {
+        libsecp256k1::PublicKey::from_secret_key(&self.secret_key).serialize_compressed()
+    }
+
+    /// First four bytes of the `HASH160` of the public key.
+    fn fingerprint(&self) -> [u8; 4] {
+        let sha = Sha256::digest(&self.public_key());
+        let hash = <ripemd160::Ripemd160 as ripemd160::Digest>::digest(&sha);
+        let mut fingerprint = [0u8; 4];
+        fingerprint.copy_from_slice(&hash[..4]);
+        fingerprint
+    }
+
+    fn derive_child(&self, index: u32) -> Result<Self, FatalProcedureError> {
+        let mut data = Vec::with_capacity(37);
+        if index >= BIP32_HARDENED {
+            data.push(0);
+            data.extend_from_slice(&self.secret_key.serialize());
+        } else {
+            data.extend_from_slice(&self.public_key());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+
+        let mut i = [0u8; SHA512_LEN];
+        HMAC_SHA512(&data, &self.chain_code, &mut i);
+        let depth = self
+            .depth
+            .checked_add(1)
+            .ok_or_else(|| FatalProcedureError::from("maximum derivation depth exceeded".to_string()))?;
+        let mut child = Self::from_hmac_output(i, depth, self.fingerprint(), index)?;
+        child
+            .secret_key
+            .tweak_add_assign(&self.secret_key)
+            .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+        Ok(child)
+    }
+
+    /// Base58Check encoded extended public key.
+    fn xpub(&self) -> String {
+        let mut bytes = Vec::with_capacity(78);
+        bytes.extend_from_slice(&BIP32_XPUB_VERSION);
+        bytes.push(self.depth);
+        bytes.extend_from_slice(&self.parent_fingerprint);
+        bytes.extend_from_slice(&self.child_number.to_be_bytes());
+        bytes.extend_from_slice(&self.chain_code);
+        bytes.extend_from_slice(&self.public_key());
+        bs58::encode(bytes).with_check().into_string()
+    }
+}
+
+/// Derive a BIP-32 child key over the secp256k1 curve from a seed or a parent key, store it in the
+/// output location and return the corresponding chain code.
+///
+/// Other than [`Slip10Derive`], this supports non-hardened segments: indices below
+/// [`BIP32_HARDENED`] are derived as normal children. The stored record contains the secret key
+/// in its first 32 bytes and is therefore compatible with [`Secp256k1EcdsaSign`] and [`PublicKey`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bip32Derive {
+    pub chain: Vec<u32>,
+
+    pub input: Slip10DeriveInput,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl DeriveSecret for Bip32Derive {
+    type Output = ChainCode;
+
+    fn derive(self, guard: GuardedVec<u8>) -> Result<Products<ChainCode>, FatalProcedureError> {
+        let mut key = match self.input {
+            Slip10DeriveInput::Key(_) => Bip32Key::from_bytes(&guard.borrow())?,
+            Slip10DeriveInput::Seed(_) => Bip32Key::from_seed(&guard.borrow())?,
+        };
+        for index in self.chain {
+            key = key.derive_child(index)?;
+        }
+        Ok(Products {
+            secret: key.to_bytes(),
+            output: key.chain_code,
+        })
+    }
+
+    fn source(&self) -> &Location {
+        match &self.input {
+            Slip10DeriveInput::Key(loc) => loc,
+            Slip10DeriveInput::Seed(loc) => loc,
+        }
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Export the Base58Check encoded extended public key (`xpub`) of a key that was derived with
+/// [`Bip32Derive`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bip32Xpub {
+    pub private_key: Location,
+}
+
+impl UseSecret for Bip32Xpub {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let key = Bip32Key::from_bytes(&guard.borrow())?;
+        Ok(key.xpub())
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
 }
 
 fn x25519_secret_key(guard: GuardedVec<u8>) -> Result<x25519::SecretKey, crypto::Error> {
@@ -465,6 +1062,31 @@ fn ed25519_secret_key(guard: GuardedVec<u8>) -> Result<ed25519::SecretKey, crypt
     Ok(ed25519::SecretKey::from_bytes(bs))
 }
 
+fn secp256k1_secret_key(guard: GuardedVec<u8>) -> Result<libsecp256k1::SecretKey, FatalProcedureError> {
+    let raw = guard.borrow();
+    if raw.len() < libsecp256k1::util::SECRET_KEY_SIZE {
+        let e = crypto::Error::BufferSize {
+            has: raw.len(),
+            needs: libsecp256k1::util::SECRET_KEY_SIZE,
+            name: "data buffer",
+        };
+        return Err(e.into());
+    }
+    libsecp256k1::SecretKey::parse_slice(&raw[..libsecp256k1::util::SECRET_KEY_SIZE])
+        .map_err(|e| FatalProcedureError::from(e.to_string()))
+}
+
+fn secp256k1_generate_secret_key() -> Result<libsecp256k1::SecretKey, crypto::Error> {
+    // Rejection sampling: retry until the random bytes are a valid scalar in `[1, n)`.
+    loop {
+        let mut bs = [0u8; libsecp256k1::util::SECRET_KEY_SIZE];
+        fill(&mut bs)?;
+        if let Ok(sk) = libsecp256k1::SecretKey::parse(&bs) {
+            return Ok(sk);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenerateKey {
     pub ty: KeyType,
@@ -472,6 +1094,8 @@ pub struct GenerateKey {
     pub output: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl GenerateSecret for GenerateKey {
@@ -481,38 +1105,164 @@ impl GenerateSecret for GenerateKey {
         let secret = match self.ty {
             KeyType::Ed25519 => ed25519::SecretKey::generate().map(|sk| sk.to_bytes().to_vec())?,
             KeyType::X25519 => x25519::SecretKey::generate().map(|sk| sk.to_bytes().to_vec())?,
+            KeyType::Secp256k1 => secp256k1_generate_secret_key().map(|sk| sk.serialize().to_vec())?,
+        };
+        Ok(Products { secret, output: () })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Import an Ed25519, X25519 or Secp256k1 private key from a PKCS#8 `PrivateKeyInfo` (RFC 5208) and write it to the
+/// specified [`Location`].
+///
+/// The key may be DER encoded or PEM encoded with the `PRIVATE KEY` or `ENCRYPTED PRIVATE KEY` label. If a `password`
+/// is set, the key is expected as PBES2 encrypted `EncryptedPrivateKeyInfo`. Ed25519 and X25519 keys use the
+/// algorithm identifiers of RFC 8410, Secp256k1 keys are expected as `ECPrivateKey` (RFC 5915) on the `secp256k1`
+/// curve.
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct ImportPkcs8 {
+    pub ty: KeyType,
+
+    pub pkcs8: Vec<u8>,
+
+    pub password: Option<Vec<u8>>,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl GenerateSecret for ImportPkcs8 {
+    type Output = ();
+
+    fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        let pem;
+        let der: &[u8] = if self.pkcs8.starts_with(b"-----BEGIN") {
+            let (label, doc) = pkcs8::der::pem::decode_vec(&self.pkcs8).map_err(|e| e.to_string())?;
+            if label != "PRIVATE KEY" && label != "ENCRYPTED PRIVATE KEY" {
+                return Err(format!("unexpected PEM label {}", label).into());
+            }
+            pem = Zeroizing::new(doc);
+            &pem
+        } else {
+            &self.pkcs8
         };
+
+        let decrypted;
+        let der = match &self.password {
+            Some(password) => {
+                let info = pkcs8::EncryptedPrivateKeyInfo::try_from(der).map_err(|e| e.to_string())?;
+                decrypted = info.decrypt(password).map_err(|e| e.to_string())?;
+                decrypted.as_bytes()
+            }
+            None => der,
+        };
+        let info = pkcs8::PrivateKeyInfo::try_from(der).map_err(|e| e.to_string())?;
+        let secret = pkcs8_private_key(&self.ty, &info)?;
+
         Ok(Products { secret, output: () })
     }
 
     fn target(&self) -> (&Location, RecordHint) {
         (&self.output, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Extract the raw private key of type `ty` from the `PrivateKeyInfo`.
+fn pkcs8_private_key(ty: &KeyType, info: &pkcs8::PrivateKeyInfo) -> Result<Vec<u8>, FatalProcedureError> {
+    use pkcs8::{
+        der::{asn1::OctetStringRef, Decode},
+        ObjectIdentifier,
+    };
+    const ID_ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+    const ID_X25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+    const ID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+    const SECP256K1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.10");
+
+    let algorithm = &info.algorithm;
+    match ty {
+        KeyType::Ed25519 | KeyType::X25519 => {
+            let oid = match ty {
+                KeyType::Ed25519 => ID_ED25519,
+                _ => ID_X25519,
+            };
+            if algorithm.oid != oid || algorithm.parameters.is_some() {
+                return Err(format!("unexpected key algorithm {}", algorithm.oid).into());
+            }
+            // The key is wrapped in a `CurvePrivateKey` octet string.
+            let key = OctetStringRef::from_der(info.private_key).map_err(|e| e.to_string())?;
+            if key.as_bytes().len() != 32 {
+                let e = crypto::Error::BufferSize {
+                    has: key.as_bytes().len(),
+                    needs: 32,
+                    name: "private key",
+                };
+                return Err(e.into());
+            }
+            Ok(key.as_bytes().to_vec())
+        }
+        KeyType::Secp256k1 => {
+            if algorithm.oid != ID_EC_PUBLIC_KEY || algorithm.parameters_oid().ok() != Some(SECP256K1) {
+                return Err(format!("unexpected key algorithm {}", algorithm.oid).into());
+            }
+            let key = sec1::EcPrivateKey::from_der(info.private_key).map_err(|e| e.to_string())?;
+            libsecp256k1::SecretKey::parse_slice(key.private_key).map_err(|e| e.to_string())?;
+            Ok(key.private_key.to_vec())
+        }
+    }
 }
 
-/// Derive an Ed25519 public key from the corresponding private key stored at the specified
-/// location
+/// Derive a public key from the corresponding private key stored at the specified
+/// location.
+///
+/// Ed25519 and X25519 public keys are returned as their 32 byte encoding, Secp256k1 public keys
+/// in the 33 byte SEC1 compressed form.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PublicKey {
     pub ty: KeyType,
 
     pub private_key: Location,
+
+    pub encoding: PublicKeyEncoding,
 }
 
 impl UseSecret for PublicKey {
-    type Output = [u8; 32];
+    type Output = Vec<u8>;
 
     fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
-        match self.ty {
+        let raw = match self.ty {
             KeyType::Ed25519 => {
                 let sk = ed25519_secret_key(guard)?;
-                Ok(sk.public_key().to_bytes())
+                sk.public_key().to_bytes().to_vec()
             }
             KeyType::X25519 => {
                 let sk = x25519_secret_key(guard)?;
-                Ok(sk.public_key().to_bytes())
+                sk.public_key().to_bytes().to_vec()
             }
-        }
+            KeyType::Secp256k1 => {
+                let sk = secp256k1_secret_key(guard)?;
+                let pk = libsecp256k1::PublicKey::from_secret_key(&sk);
+                match self.encoding {
+                    // SubjectPublicKeyInfo of EC keys commonly contains the uncompressed point.
+                    PublicKeyEncoding::SpkiDer | PublicKeyEncoding::SpkiPem => pk.serialize().to_vec(),
+                    _ => pk.serialize_compressed().to_vec(),
+                }
+            }
+        };
+        Ok(self.encoding.encode(&self.ty, raw))
     }
 
     fn source(&self) -> &Location {
@@ -520,22 +1270,105 @@ impl UseSecret for PublicKey {
     }
 }
 
-/// Use the specified Ed25519 compatible key to sign the given message
-///
-/// Compatible keys are any record that contain the desired key material in the first 32 bytes,
-/// in particular SLIP10 keys are compatible.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Ed25519Sign {
-    pub msg: Vec<u8>,
-
-    pub private_key: Location,
+/// Encoding of the public key that is returned by the [`PublicKey`] procedure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PublicKeyEncoding {
+    /// The raw key bytes. Secp256k1 keys are returned as compressed point.
+    Raw,
+    /// UTF-8 string of the multibase base58btc encoding (`z` prefix) of the key with its multicodec prefix, as it is
+    /// used for `publicKeyMultibase` in DID documents.
+    Multibase,
+    /// DER encoded `SubjectPublicKeyInfo` as defined in RFC 5280, with the algorithm identifiers of RFC 8410 for
+    /// Ed25519 / X25519 and of RFC 5480 for Secp256k1.
+    SpkiDer,
+    /// UTF-8 string of the PEM encoding (`PUBLIC KEY` label) of the [`PublicKeyEncoding::SpkiDer`] encoding.
+    SpkiPem,
 }
 
-impl UseSecret for Ed25519Sign {
-    type Output = [u8; ed25519::SIGNATURE_LENGTH];
-
-    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
-        let sk = ed25519_secret_key(guard)?;
+impl PublicKeyEncoding {
+    fn encode(&self, ty: &KeyType, raw: Vec<u8>) -> Vec<u8> {
+        match self {
+            PublicKeyEncoding::Raw => raw,
+            PublicKeyEncoding::Multibase => {
+                let codec: &[u8] = match ty {
+                    KeyType::Ed25519 => &[0xed, 0x01],
+                    KeyType::X25519 => &[0xec, 0x01],
+                    KeyType::Secp256k1 => &[0xe7, 0x01],
+                };
+                let mut encoded = String::from("z");
+                encoded.push_str(&bs58::encode([codec, &raw].concat()).into_string());
+                encoded.into_bytes()
+            }
+            PublicKeyEncoding::SpkiDer => {
+                let header: &[u8] = match ty {
+                    // SEQUENCE { SEQUENCE { OID 1.3.101.112 }, BIT STRING }
+                    KeyType::Ed25519 => &[0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00],
+                    // SEQUENCE { SEQUENCE { OID 1.3.101.110 }, BIT STRING }
+                    KeyType::X25519 => &[0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x6e, 0x03, 0x21, 0x00],
+                    // SEQUENCE { SEQUENCE { OID 1.2.840.10045.2.1, OID 1.3.132.0.10 }, BIT STRING }
+                    KeyType::Secp256k1 => &[
+                        0x30, 0x56, 0x30, 0x10, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x05, 0x2b,
+                        0x81, 0x04, 0x00, 0x0a, 0x03, 0x42, 0x00,
+                    ],
+                };
+                [header, &raw].concat()
+            }
+            PublicKeyEncoding::SpkiPem => {
+                let der = PublicKeyEncoding::SpkiDer.encode(ty, raw);
+                // Encoding only fails for labels that are not valid in PEM.
+                pkcs8::der::pem::encode_string("PUBLIC KEY", pkcs8::der::pem::LineEnding::LF, &der)
+                    .unwrap_or_default()
+                    .into_bytes()
+            }
+        }
+    }
+}
+
+/// Verify an Ed25519 signature over the given message with the public key. This procedure does not access the
+/// vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ed25519Verify {
+    pub public_key: Vec<u8>,
+
+    pub msg: Vec<u8>,
+
+    pub signature: Vec<u8>,
+}
+
+impl Procedure for Ed25519Verify {
+    type Output = bool;
+
+    fn execute<R: Runner>(self, _runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let public_key: [u8; ed25519::PUBLIC_KEY_LENGTH] = self
+            .public_key
+            .try_into()
+            .map_err(|_| FatalProcedureError::from("invalid ed25519 public key length".to_string()))?;
+        let signature: [u8; ed25519::SIGNATURE_LENGTH] = self
+            .signature
+            .try_into()
+            .map_err(|_| FatalProcedureError::from("invalid ed25519 signature length".to_string()))?;
+        let pk = ed25519::PublicKey::try_from_bytes(public_key).map_err(FatalProcedureError::from)?;
+        let sig = ed25519::Signature::from_bytes(signature);
+        Ok(pk.verify(&sig, &self.msg))
+    }
+}
+
+/// Use the specified Ed25519 compatible key to sign the given message
+///
+/// Compatible keys are any record that contain the desired key material in the first 32 bytes,
+/// in particular SLIP10 keys are compatible.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ed25519Sign {
+    pub msg: Vec<u8>,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for Ed25519Sign {
+    type Output = [u8; ed25519::SIGNATURE_LENGTH];
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = ed25519_secret_key(guard)?;
         let sig = sk.sign(&self.msg);
         Ok(sig.to_bytes())
     }
@@ -545,6 +1378,206 @@ impl UseSecret for Ed25519Sign {
     }
 }
 
+/// Use the specified Secp256k1 compatible key to create a recoverable ECDSA signature over an
+/// already hashed 32 byte message (e.g. the Keccak256 digest of an Ethereum transaction).
+///
+/// The output is the 64 byte compact signature `r || s` followed by the recovery id (0 or 1).
+/// Compatible keys are any record that contain the desired key material in the first 32 bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Secp256k1EcdsaSign {
+    pub msg: [u8; libsecp256k1::util::MESSAGE_SIZE],
+
+    pub private_key: Location,
+}
+
+impl UseSecret for Secp256k1EcdsaSign {
+    type Output = [u8; libsecp256k1::util::SIGNATURE_SIZE + 1];
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let sk = secp256k1_secret_key(guard)?;
+        let msg = libsecp256k1::Message::parse(&self.msg);
+        let (sig, recovery_id) = libsecp256k1::sign(&msg, &sk);
+        let mut output = [0u8; libsecp256k1::util::SIGNATURE_SIZE + 1];
+        output[..libsecp256k1::util::SIGNATURE_SIZE].copy_from_slice(&sig.serialize());
+        output[libsecp256k1::util::SIGNATURE_SIZE] = recovery_id.serialize();
+        Ok(output)
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Signature algorithm of a JSON Web Signature (RFC 7515).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JwsAlgorithm {
+    /// Ed25519 signature as defined in RFC 8037.
+    EdDSA,
+    /// ECDSA on the secp256k1 curve with SHA-256 as defined in RFC 8812.
+    ES256K,
+}
+
+impl JwsAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            JwsAlgorithm::EdDSA => "EdDSA",
+            JwsAlgorithm::ES256K => "ES256K",
+        }
+    }
+}
+
+enum JwsKey {
+    Ed25519(Box<ed25519::SecretKey>),
+    Secp256k1(libsecp256k1::SecretKey),
+}
+
+impl JwsKey {
+    /// JWK thumbprint (RFC 7638) of the public key.
+    fn thumbprint(&self) -> String {
+        let b64 = |data: &[u8]| data_encoding::BASE64URL_NOPAD.encode(data);
+        let jwk = match self {
+            JwsKey::Ed25519(sk) => format!(
+                r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+                b64(&sk.public_key().to_bytes())
+            ),
+            JwsKey::Secp256k1(sk) => {
+                let point = libsecp256k1::PublicKey::from_secret_key(sk).serialize();
+                format!(
+                    r#"{{"crv":"secp256k1","kty":"EC","x":"{}","y":"{}"}}"#,
+                    b64(&point[1..33]),
+                    b64(&point[33..])
+                )
+            }
+        };
+        b64(&Sha256::digest(jwk.as_bytes()))
+    }
+
+    fn sign(&self, signing_input: &[u8]) -> Vec<u8> {
+        match self {
+            JwsKey::Ed25519(sk) => sk.sign(signing_input).to_bytes().to_vec(),
+            JwsKey::Secp256k1(sk) => {
+                let msg = libsecp256k1::Message::parse(&jws_es256k_digest(signing_input));
+                let (sig, _) = libsecp256k1::sign(&msg, sk);
+                sig.serialize().to_vec()
+            }
+        }
+    }
+}
+
+fn jws_es256k_digest(signing_input: &[u8]) -> [u8; SHA256_LEN] {
+    let mut digest = [0u8; SHA256_LEN];
+    digest.copy_from_slice(&Sha256::digest(signing_input));
+    digest
+}
+
+/// Sign a JSON Web Token with the key stored at the specified location and return the JWS compact serialization
+/// `header.payload.signature` (RFC 7515).
+///
+/// The `alg` and `kid` parameters of the protected header are set from the key, the `kid` is the JWK thumbprint
+/// (RFC 7638) of its public key. The claims are used as payload without modification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwsSign {
+    pub alg: JwsAlgorithm,
+
+    /// JSON object with additional header parameters, e.g. `{"typ":"JWT"}`.
+    pub header: String,
+
+    /// JSON claims set.
+    pub claims: String,
+
+    pub private_key: Location,
+}
+
+impl UseSecret for JwsSign {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        let mut header: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&self.header).map_err(|e| format!("invalid JWS header: {}", e))?;
+        serde_json::from_str::<serde::de::IgnoredAny>(&self.claims)
+            .map_err(|e| format!("invalid JWT claims: {}", e))?;
+
+        let key = match self.alg {
+            JwsAlgorithm::EdDSA => JwsKey::Ed25519(Box::new(ed25519_secret_key(guard)?)),
+            JwsAlgorithm::ES256K => JwsKey::Secp256k1(secp256k1_secret_key(guard)?),
+        };
+        header.insert("alg".into(), self.alg.name().into());
+        header.insert("kid".into(), key.thumbprint().into());
+        let header = serde_json::to_vec(&header).map_err(|e| e.to_string())?;
+
+        let b64 = |data: &[u8]| data_encoding::BASE64URL_NOPAD.encode(data);
+        let signing_input = format!("{}.{}", b64(&header), b64(self.claims.as_bytes()));
+        let signature = key.sign(signing_input.as_bytes());
+        Ok(format!("{}.{}", signing_input, b64(&signature)))
+    }
+
+    fn source(&self) -> &Location {
+        &self.private_key
+    }
+}
+
+/// Verify the signature of a JWS in compact serialization with the given public key. The output is `false` if the
+/// `alg` of the header does not match the expected algorithm. This procedure does not access the vault.
+///
+/// Ed25519 public keys are expected as their 32 byte encoding, Secp256k1 public keys in SEC1 compressed or
+/// uncompressed form.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwsVerify {
+    pub alg: JwsAlgorithm,
+
+    pub jws: String,
+
+    pub public_key: Vec<u8>,
+}
+
+impl Procedure for JwsVerify {
+    type Output = bool;
+
+    fn execute<R: Runner>(self, _runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let invalid = || FatalProcedureError::from("invalid JWS compact serialization".to_string());
+        let decode = |data: &str| {
+            data_encoding::BASE64URL_NOPAD
+                .decode(data.as_bytes())
+                .map_err(|_| invalid())
+        };
+
+        let (signing_input, signature) = self.jws.rsplit_once('.').ok_or_else(invalid)?;
+        let (header, _payload) = signing_input.split_once('.').ok_or_else(invalid)?;
+        let header: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_slice(&decode(header)?).map_err(|_| invalid())?;
+        let signature = decode(signature)?;
+        if header.get("alg").and_then(|alg| alg.as_str()) != Some(self.alg.name()) {
+            return Ok(false);
+        }
+
+        match self.alg {
+            JwsAlgorithm::EdDSA => {
+                let public_key: [u8; ed25519::PUBLIC_KEY_LENGTH] = self
+                    .public_key
+                    .try_into()
+                    .map_err(|_| FatalProcedureError::from("invalid ed25519 public key length".to_string()))?;
+                let pk = ed25519::PublicKey::try_from_bytes(public_key).map_err(FatalProcedureError::from)?;
+                let signature: [u8; ed25519::SIGNATURE_LENGTH] = match signature.try_into() {
+                    Ok(signature) => signature,
+                    Err(_) => return Ok(false),
+                };
+                let sig = ed25519::Signature::from_bytes(signature);
+                Ok(pk.verify(&sig, signing_input.as_bytes()))
+            }
+            JwsAlgorithm::ES256K => {
+                let pk = libsecp256k1::PublicKey::parse_slice(&self.public_key, None)
+                    .map_err(|e| FatalProcedureError::from(e.to_string()))?;
+                let sig = match libsecp256k1::Signature::parse_standard_slice(&signature) {
+                    Ok(sig) => sig,
+                    Err(_) => return Ok(false),
+                };
+                let msg = libsecp256k1::Message::parse(&jws_es256k_digest(signing_input.as_bytes()));
+                Ok(libsecp256k1::verify(&msg, &sig, &pk))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct X25519DiffieHellman {
     pub public_key: [u8; x25519::PUBLIC_KEY_LENGTH],
@@ -554,6 +1587,8 @@ pub struct X25519DiffieHellman {
     pub shared_key: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl DeriveSecret for X25519DiffieHellman {
@@ -577,6 +1612,10 @@ impl DeriveSecret for X25519DiffieHellman {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.shared_key, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -616,6 +1655,224 @@ impl UseSecret for Hmac {
     }
 }
 
+/// Hash function of the HMAC that is used to compute one-time passwords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    fn hmac(&self, key: &[u8], msg: &[u8]) -> Result<Vec<u8>, FatalProcedureError> {
+        match self {
+            OtpAlgorithm::Sha1 => {
+                // iota-crypto does not provide HMAC-SHA1, which is still the default algorithm of most OTP issuers.
+                use hmac::{Mac, NewMac};
+                let mut mac = hmac::Hmac::<sha1::Sha1>::new_from_slice(key).map_err(|e| e.to_string())?;
+                mac.update(msg);
+                Ok(mac.finalize().into_bytes().to_vec())
+            }
+            OtpAlgorithm::Sha256 => {
+                let mut mac = [0; SHA256_LEN];
+                HMAC_SHA256(msg, key, &mut mac);
+                Ok(mac.to_vec())
+            }
+            OtpAlgorithm::Sha512 => {
+                let mut mac = [0; SHA512_LEN];
+                HMAC_SHA512(msg, key, &mut mac);
+                Ok(mac.to_vec())
+            }
+        }
+    }
+}
+
+/// Compute the HOTP value of RFC 4226 with the given number of digits.
+fn hotp(algorithm: OtpAlgorithm, key: &[u8], counter: u64, digits: u32) -> Result<String, FatalProcedureError> {
+    if !(1..=10).contains(&digits) {
+        return Err(format!("invalid number of OTP digits {}", digits).into());
+    }
+    let mac = algorithm.hmac(key, &counter.to_be_bytes())?;
+    // Dynamic truncation of the MAC.
+    let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([mac[offset], mac[offset + 1], mac[offset + 2], mac[offset + 3]]) & 0x7fff_ffff;
+    let code = binary as u64 % 10u64.pow(digits);
+    Ok(format!("{:0width$}", code, width = digits as usize))
+}
+
+/// Compute the HMAC-based one-time password (RFC 4226) for the counter with the key stored at the specified location.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hotp {
+    pub algorithm: OtpAlgorithm,
+
+    pub digits: u32,
+
+    pub counter: u64,
+
+    pub key: Location,
+}
+
+impl UseSecret for Hotp {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        hotp(self.algorithm, &guard.borrow(), self.counter, self.digits)
+    }
+
+    fn source(&self) -> &Location {
+        &self.key
+    }
+}
+
+/// Compute the time-based one-time password (RFC 6238) for the given unix `timestamp` with the key stored at the
+/// specified location.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Totp {
+    pub algorithm: OtpAlgorithm,
+
+    pub digits: u32,
+
+    /// Time step in seconds.
+    pub period: u64,
+
+    /// Unix time in seconds for which the password is computed.
+    pub timestamp: u64,
+
+    pub key: Location,
+}
+
+impl UseSecret for Totp {
+    type Output = String;
+
+    fn use_secret(self, guard: GuardedVec<u8>) -> Result<Self::Output, FatalProcedureError> {
+        if self.period == 0 {
+            return Err("invalid TOTP period 0".to_string().into());
+        }
+        hotp(
+            self.algorithm,
+            &guard.borrow(),
+            self.timestamp / self.period,
+            self.digits,
+        )
+    }
+
+    fn source(&self) -> &Location {
+        &self.key
+    }
+}
+
+/// Type of the one-time password of an `otpauth://` URI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtpKind {
+    Hotp { counter: u64 },
+    Totp { period: u64 },
+}
+
+/// Parameters of an `otpauth://` URI that was imported with [`ImportOtpauth`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OtpParameters {
+    pub kind: OtpKind,
+
+    pub algorithm: OtpAlgorithm,
+
+    pub digits: u32,
+
+    pub issuer: Option<String>,
+
+    pub account: String,
+}
+
+/// Import the base32 encoded shared secret of an `otpauth://totp/..` or `otpauth://hotp/..` URI, as it is used in
+/// the QR codes of 2FA setups, into the specified [`Location`]. The remaining parameters of the URI are returned, so
+/// that they can be used for [`Totp`] and [`Hotp`].
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct ImportOtpauth {
+    pub uri: String,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl GenerateSecret for ImportOtpauth {
+    type Output = OtpParameters;
+
+    fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        let invalid = |e: &str| FatalProcedureError::from(format!("invalid otpauth URI: {}", e));
+
+        let uri = url::Url::parse(&self.uri).map_err(|e| invalid(&e.to_string()))?;
+        if uri.scheme() != "otpauth" {
+            return Err(invalid("unexpected scheme"));
+        }
+        let label = percent_encoding::percent_decode_str(uri.path().trim_start_matches('/'))
+            .decode_utf8()
+            .map_err(|e| invalid(&e.to_string()))?;
+        let (label_issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(issuer.to_string()), account.trim_start().to_string()),
+            None => (None, label.to_string()),
+        };
+
+        let mut secret = None;
+        let mut issuer = None;
+        let mut algorithm = OtpAlgorithm::Sha1;
+        let mut digits = 6;
+        let mut counter = None;
+        let mut period = 30;
+        for (key, value) in uri.query_pairs() {
+            match key.as_ref() {
+                "secret" => {
+                    let encoded = value.trim_end_matches('=').to_ascii_uppercase();
+                    let decoded = data_encoding::BASE32_NOPAD
+                        .decode(encoded.as_bytes())
+                        .map_err(|_| invalid("secret is not base32 encoded"))?;
+                    secret = Some(decoded);
+                }
+                "issuer" => issuer = Some(value.into_owned()),
+                "algorithm" => {
+                    algorithm = match value.as_ref() {
+                        "SHA1" => OtpAlgorithm::Sha1,
+                        "SHA256" => OtpAlgorithm::Sha256,
+                        "SHA512" => OtpAlgorithm::Sha512,
+                        _ => return Err(invalid("unsupported algorithm")),
+                    }
+                }
+                "digits" => digits = value.parse().map_err(|_| invalid("invalid digits"))?,
+                "counter" => counter = Some(value.parse().map_err(|_| invalid("invalid counter"))?),
+                "period" => period = value.parse().map_err(|_| invalid("invalid period"))?,
+                _ => {}
+            }
+        }
+
+        let kind = match uri.host_str() {
+            Some("hotp") => OtpKind::Hotp {
+                counter: counter.ok_or_else(|| invalid("missing counter"))?,
+            },
+            Some("totp") if period > 0 => OtpKind::Totp { period },
+            Some("totp") => return Err(invalid("invalid period")),
+            _ => return Err(invalid("unexpected type")),
+        };
+        let secret = secret.ok_or_else(|| invalid("missing secret"))?;
+        let output = OtpParameters {
+            kind,
+            algorithm,
+            digits,
+            issuer: issuer.or(label_issuer),
+            account,
+        };
+        Ok(Products { secret, output })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Hkdf {
     pub hash_type: Sha2Hash,
@@ -629,6 +1886,8 @@ pub struct Hkdf {
     pub okm: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl DeriveSecret for Hkdf {
@@ -668,6 +1927,10 @@ impl DeriveSecret for Hkdf {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.okm, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -683,6 +1946,8 @@ pub struct Pbkdf2Hmac {
     pub output: Location,
 
     pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
 }
 
 impl GenerateSecret for Pbkdf2Hmac {
@@ -712,6 +1977,165 @@ impl GenerateSecret for Pbkdf2Hmac {
     fn target(&self) -> (&Location, RecordHint) {
         (&self.output, self.hint)
     }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Maximal length in bytes of the keys derived by [`Argon2`] and [`Scrypt`].
+pub const PASSWORD_HASH_MAX_KEY_LEN: usize = 1024;
+
+/// Maximal base 2 logarithm of the cost `N` of [`Scrypt`].
+pub const SCRYPT_MAX_LOG_N: u8 = 20;
+
+/// Maximal memory in bytes that [`Scrypt`] may use, which is `128 * r * N` (4 GiB).
+pub const SCRYPT_MAX_MEMORY: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Maximal degree of parallelism of [`Scrypt`].
+pub const SCRYPT_MAX_PARALLELISM: u32 = 16;
+
+fn check_key_len(key_len: usize) -> Result<(), FatalProcedureError> {
+    if key_len > PASSWORD_HASH_MAX_KEY_LEN {
+        return Err(format!(
+            "key length {} exceeds the maximum of {}",
+            key_len, PASSWORD_HASH_MAX_KEY_LEN
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Variant of the Argon2 function that is used by the [`Argon2`] procedure.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Argon2Variant {
+    Argon2d,
+    Argon2i,
+    Argon2id,
+}
+
+/// Derive a key from a password with the memory-hard Argon2 function and write it to the specified [`Location`].
+///
+/// The costs may not exceed the bounds that also apply to the key derivation of snapshots, i.e. 256 MiB of memory,
+/// 16 iterations and a parallelism of 16, and the key may be at most [`PASSWORD_HASH_MAX_KEY_LEN`] bytes long.
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct Argon2 {
+    pub variant: Argon2Variant,
+
+    pub password: Vec<u8>,
+
+    pub salt: Vec<u8>,
+
+    /// Memory size in KiB.
+    pub memory_cost: u32,
+
+    /// Number of iterations.
+    pub time_cost: u32,
+
+    /// Degree of parallelism.
+    pub parallelism: u32,
+
+    /// Length of the derived key in bytes.
+    pub key_len: usize,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl GenerateSecret for Argon2 {
+    type Output = ();
+
+    fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        let algorithm = match self.variant {
+            Argon2Variant::Argon2d => argon2::Algorithm::Argon2d,
+            Argon2Variant::Argon2i => argon2::Algorithm::Argon2i,
+            Argon2Variant::Argon2id => argon2::Algorithm::Argon2id,
+        };
+        check_key_len(self.key_len)?;
+        Argon2Params {
+            memory_cost: self.memory_cost,
+            time_cost: self.time_cost,
+            parallelism: self.parallelism,
+        }
+        .check_bounds()
+        .map_err(|e| e.to_string())?;
+        let params = argon2::Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(self.key_len))
+            .map_err(|e| e.to_string())?;
+        let mut secret = vec![0; self.key_len];
+        argon2::Argon2::new(algorithm, argon2::Version::V0x13, params)
+            .hash_password_into(&self.password, &self.salt, &mut secret)
+            .map_err(|e| e.to_string())?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Derive a key from a password with the memory-hard scrypt function and write it to the specified [`Location`].
+///
+/// `log_n` may be at most [`SCRYPT_MAX_LOG_N`], `p` at most [`SCRYPT_MAX_PARALLELISM`], the used memory at most
+/// [`SCRYPT_MAX_MEMORY`] and the key at most [`PASSWORD_HASH_MAX_KEY_LEN`] bytes long.
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct Scrypt {
+    pub password: Vec<u8>,
+
+    pub salt: Vec<u8>,
+
+    /// Base 2 logarithm of the CPU/ memory cost `N`.
+    pub log_n: u8,
+
+    /// Block size.
+    pub r: u32,
+
+    /// Degree of parallelism.
+    pub p: u32,
+
+    /// Length of the derived key in bytes.
+    pub key_len: usize,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl GenerateSecret for Scrypt {
+    type Output = ();
+
+    fn generate(self) -> Result<Products<Self::Output>, FatalProcedureError> {
+        check_key_len(self.key_len)?;
+        if self.log_n > SCRYPT_MAX_LOG_N {
+            return Err(format!("log_n {} exceeds the maximum of {}", self.log_n, SCRYPT_MAX_LOG_N).into());
+        }
+        if self.p > SCRYPT_MAX_PARALLELISM {
+            return Err(format!("p {} exceeds the maximum of {}", self.p, SCRYPT_MAX_PARALLELISM).into());
+        }
+        if (128 * u64::from(self.r)) << self.log_n > SCRYPT_MAX_MEMORY {
+            return Err("memory cost exceeds the maximum".to_string().into());
+        }
+        let params = scrypt::Params::new(self.log_n, self.r, self.p).map_err(|e| e.to_string())?;
+        let mut secret = vec![0; self.key_len];
+        scrypt::scrypt(&self.password, &self.salt, &params, &mut secret).map_err(|e| e.to_string())?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -802,3 +2226,501 @@ impl UseSecret for AeadDecrypt {
         &self.key
     }
 }
+
+/// Algorithm that is used to wrap a key with [`WrapKey`] and [`UnwrapKey`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum WrapAlgorithm {
+    /// AES Key Wrap as defined in RFC 3394. The key-encryption key has to be 16, 24 or 32 bytes long, the wrapped key
+    /// a multiple of 8 bytes and at least 16 bytes long.
+    AesKw,
+    /// Authenticated encryption with a random nonce. The wrapped blob is `nonce || tag || ciphertext`.
+    Aead(AeadCipher),
+}
+
+/// Runs the expression with the AES-KW instance for the size of the key-encryption key.
+macro_rules! with_kek {
+    ($kek:expr, |$k:ident| $body:expr) => {
+        match $kek.len() {
+            16 => {
+                let $k = Aes128Kw::new($kek);
+                $body.map_err(FatalProcedureError::from)
+            }
+            24 => {
+                let $k = Aes192Kw::new($kek);
+                $body.map_err(FatalProcedureError::from)
+            }
+            32 => {
+                let $k = Aes256Kw::new($kek);
+                $body.map_err(FatalProcedureError::from)
+            }
+            len => Err(FatalProcedureError::from(format!(
+                "key-encryption key has invalid length {}",
+                len
+            ))),
+        }
+    };
+}
+
+impl WrapAlgorithm {
+    fn wrap(&self, kek: &[u8], key: &[u8]) -> Result<Vec<u8>, FatalProcedureError> {
+        match self {
+            WrapAlgorithm::AesKw => {
+                if key.len() < 2 * AES_KW_BLOCK || !key.len().is_multiple_of(AES_KW_BLOCK) {
+                    return Err(FatalProcedureError::from(
+                        "key has to be a multiple of 8 bytes and at least 16 bytes long".to_string(),
+                    ));
+                }
+                let mut wrapped = vec![0; key.len() + AES_KW_BLOCK];
+                with_kek!(kek, |k| k.wrap_key(key, &mut wrapped))?;
+                Ok(wrapped)
+            }
+            WrapAlgorithm::Aead(cipher) => {
+                let f = match cipher {
+                    AeadCipher::Aes256Gcm => Aes256Gcm::try_encrypt,
+                    AeadCipher::XChaCha20Poly1305 => XChaCha20Poly1305::try_encrypt,
+                };
+                let nonce_len = match cipher {
+                    AeadCipher::Aes256Gcm => Aes256Gcm::NONCE_LENGTH,
+                    AeadCipher::XChaCha20Poly1305 => XChaCha20Poly1305::NONCE_LENGTH,
+                };
+                let mut tag = match cipher {
+                    AeadCipher::Aes256Gcm => Tag::<Aes256Gcm>::default(),
+                    AeadCipher::XChaCha20Poly1305 => Tag::<XChaCha20Poly1305>::default(),
+                };
+                let mut nonce = vec![0; nonce_len];
+                fill(&mut nonce)?;
+                let mut ctx = vec![0; key.len()];
+                f(kek, &nonce, &[], key, &mut ctx, &mut tag)?;
+                Ok([nonce, tag.to_vec(), ctx].concat())
+            }
+        }
+    }
+
+    fn unwrap(&self, kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, FatalProcedureError> {
+        match self {
+            WrapAlgorithm::AesKw => {
+                if wrapped.len() < 3 * AES_KW_BLOCK || !wrapped.len().is_multiple_of(AES_KW_BLOCK) {
+                    return Err(FatalProcedureError::from(
+                        "wrapped key has to be a multiple of 8 bytes and at least 24 bytes long".to_string(),
+                    ));
+                }
+                let mut key = vec![0; wrapped.len() - AES_KW_BLOCK];
+                with_kek!(kek, |k| k.unwrap_key(wrapped, &mut key))?;
+                Ok(key)
+            }
+            WrapAlgorithm::Aead(cipher) => {
+                let f = match cipher {
+                    AeadCipher::Aes256Gcm => Aes256Gcm::try_decrypt,
+                    AeadCipher::XChaCha20Poly1305 => XChaCha20Poly1305::try_decrypt,
+                };
+                let (nonce_len, tag_len) = match cipher {
+                    AeadCipher::Aes256Gcm => (Aes256Gcm::NONCE_LENGTH, Aes256Gcm::TAG_LENGTH),
+                    AeadCipher::XChaCha20Poly1305 => (XChaCha20Poly1305::NONCE_LENGTH, XChaCha20Poly1305::TAG_LENGTH),
+                };
+                if wrapped.len() < nonce_len + tag_len {
+                    return Err(FatalProcedureError::from("wrapped key is too short".to_string()));
+                }
+                let (nonce, rest) = wrapped.split_at(nonce_len);
+                let (tag, ctx) = rest.split_at(tag_len);
+                let mut ptx = vec![0; ctx.len()];
+                f(kek, nonce, &[], &mut ptx, ctx, tag)?;
+                Ok(ptx)
+            }
+        }
+    }
+}
+
+/// Wrap the key at `key` with the key-encryption key at `wrapping_key` and return the wrapped blob, e.g. to migrate
+/// the key to another device. The wrapped blob can be imported with [`UnwrapKey`].
+///
+/// Note: This exports the key from the vault and therefore requires [`ProcedureKind::Copy`] access to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrapKey {
+    pub algorithm: WrapAlgorithm,
+
+    pub wrapping_key: Location,
+
+    pub key: Location,
+}
+
+impl Procedure for WrapKey {
+    type Output = Vec<u8>;
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let kek = runner.get_guard(&self.wrapping_key, ProcedureKind::Use, |guard| {
+            Ok(Zeroizing::new((*guard.borrow()).to_vec()))
+        })?;
+        let algorithm = self.algorithm;
+        runner.get_guard(&self.key, ProcedureKind::Copy, |guard| {
+            algorithm.wrap(&kek, &guard.borrow())
+        })
+    }
+}
+
+/// Unwrap a key that was wrapped with [`WrapKey`] or by another device, and write it into `output`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnwrapKey {
+    pub algorithm: WrapAlgorithm,
+
+    pub wrapping_key: Location,
+
+    pub wrapped_key: Vec<u8>,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl DeriveSecret for UnwrapKey {
+    type Output = ();
+
+    fn derive(self, guard: GuardedVec<u8>) -> Result<Products<()>, FatalProcedureError> {
+        let secret = self.algorithm.unwrap(&guard.borrow(), &self.wrapped_key)?;
+        Ok(Products { secret, output: () })
+    }
+
+    fn source(&self) -> &Location {
+        &self.wrapping_key
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Info that binds the key of a sealed box to its purpose.
+const SEALED_BOX_INFO: &[u8] = b"stronghold-sealed-box";
+
+/// Derives the XChaCha20-Poly1305 key of a sealed box from the X25519 shared secret with HKDF-SHA256, using the
+/// ephemeral and the recipient public key as salt.
+fn sealed_box_key(shared_secret: &[u8], ephemeral_pk: &[u8], recipient_pk: &[u8]) -> [u8; 32] {
+    let mut key = [0; 32];
+    hkdf::Hkdf::<Sha256>::new(Some(&[ephemeral_pk, recipient_pk].concat()), shared_secret)
+        .expand(SEALED_BOX_INFO, &mut key)
+        .expect("key is the correct length");
+    key
+}
+
+/// Anonymously encrypt the plaintext to the X25519 public key of a recipient. A new ephemeral keypair is generated
+/// for each encryption, the key is derived from the shared secret with HKDF-SHA256 and the plaintext is encrypted
+/// with XChaCha20-Poly1305.
+///
+/// The output is `ephemeral public key || nonce || tag || ciphertext` and can be opened with [`SealedBoxDecrypt`].
+/// This procedure does not access the vault.
+#[derive(GuardDebug, Clone, Serialize, Deserialize)]
+pub struct SealedBoxEncrypt {
+    pub public_key: [u8; x25519::PUBLIC_KEY_LENGTH],
+
+    pub associated_data: Vec<u8>,
+
+    pub plaintext: Vec<u8>,
+}
+
+impl SealedBoxEncrypt {
+    fn seal(self) -> Result<Vec<u8>, FatalProcedureError> {
+        let ephemeral = x25519::SecretKey::generate()?;
+        let ephemeral_pk = ephemeral.public_key().to_bytes();
+        let shared = ephemeral.diffie_hellman(&x25519::PublicKey::from_bytes(self.public_key));
+        let key = sealed_box_key(&shared.to_bytes(), &ephemeral_pk, &self.public_key);
+
+        let mut nonce = [0; XChaCha20Poly1305::NONCE_LENGTH];
+        fill(&mut nonce)?;
+        let mut ctx = vec![0; self.plaintext.len()];
+        let mut tag = Tag::<XChaCha20Poly1305>::default();
+        XChaCha20Poly1305::try_encrypt(&key, &nonce, &self.associated_data, &self.plaintext, &mut ctx, &mut tag)?;
+
+        let mut output = Vec::with_capacity(ephemeral_pk.len() + nonce.len() + tag.len() + ctx.len());
+        output.extend(ephemeral_pk);
+        output.extend(nonce);
+        output.extend(tag);
+        output.extend(ctx);
+        Ok(output)
+    }
+}
+
+impl Procedure for SealedBoxEncrypt {
+    type Output = Vec<u8>;
+
+    fn execute<R: Runner>(self, _runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let output = self.seal()?;
+        Ok(output)
+    }
+}
+
+/// Open a box that was sealed with [`SealedBoxEncrypt`] to the public key of the X25519 private key at
+/// `private_key`. If an `output` location is given, the plaintext is written as new secret into it, otherwise it is
+/// returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedBoxDecrypt {
+    pub ciphertext: Vec<u8>,
+
+    pub associated_data: Vec<u8>,
+
+    pub private_key: Location,
+
+    pub output: Option<(Location, RecordHint)>,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl SealedBoxDecrypt {
+    fn open(sk: x25519::SecretKey, sealed: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, FatalProcedureError> {
+        let header_len = x25519::PUBLIC_KEY_LENGTH + XChaCha20Poly1305::NONCE_LENGTH + XChaCha20Poly1305::TAG_LENGTH;
+        if sealed.len() < header_len {
+            return Err(FatalProcedureError::from("sealed box is too short".to_string()));
+        }
+        let (ephemeral_pk, rest) = sealed.split_at(x25519::PUBLIC_KEY_LENGTH);
+        let (nonce, rest) = rest.split_at(XChaCha20Poly1305::NONCE_LENGTH);
+        let (tag, ctx) = rest.split_at(XChaCha20Poly1305::TAG_LENGTH);
+
+        let mut public = [0; x25519::PUBLIC_KEY_LENGTH];
+        public.copy_from_slice(ephemeral_pk);
+        let shared = sk.diffie_hellman(&x25519::PublicKey::from_bytes(public));
+        let key = sealed_box_key(&shared.to_bytes(), ephemeral_pk, &sk.public_key().to_bytes());
+
+        let mut ptx = vec![0; ctx.len()];
+        XChaCha20Poly1305::try_decrypt(&key, nonce, associated_data, &mut ptx, ctx, tag)?;
+        Ok(ptx)
+    }
+}
+
+impl Procedure for SealedBoxDecrypt {
+    type Output = Vec<u8>;
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let SealedBoxDecrypt {
+            ciphertext,
+            associated_data,
+            private_key,
+            output,
+            lifetime,
+        } = self;
+        let open = |guard: GuardedVec<u8>| -> Result<Vec<u8>, FatalProcedureError> {
+            let sk = x25519_secret_key(guard)?;
+            Self::open(sk, &ciphertext, &associated_data)
+        };
+        match output {
+            Some((location, hint)) => {
+                let f = |guard| {
+                    Ok(Products {
+                        secret: open(guard)?,
+                        output: Vec::new(),
+                    })
+                };
+                runner.exec_proc(&private_key, &location, hint, lifetime, ProcedureKind::Derive, f)
+            }
+            None => runner.get_guard(&private_key, ProcedureKind::Use, open),
+        }
+    }
+}
+
+/// Data that is hashed by the [`Hash`] procedure.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HashInput {
+    /// The secret at the given location.
+    Secret(Location),
+    /// A non-secret message.
+    Message(Vec<u8>),
+}
+
+/// Compute the digest of a secret or of a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hash {
+    pub ty: HashType,
+
+    pub input: HashInput,
+}
+
+impl Procedure for Hash {
+    type Output = Vec<u8>;
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let ty = self.ty;
+        match self.input {
+            HashInput::Secret(location) => {
+                runner.get_guard(&location, ProcedureKind::Use, |guard| Ok(ty.digest(&guard.borrow())))
+            }
+            HashInput::Message(msg) => Ok(ty.digest(&msg)),
+        }
+    }
+}
+
+/// Compute the digest of a secret and write it as new secret into `output`, e.g. as fingerprint of a key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashSecret {
+    pub ty: HashType,
+
+    pub input: Location,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl DeriveSecret for HashSecret {
+    type Output = ();
+
+    fn derive(self, guard: GuardedVec<u8>) -> Result<Products<()>, FatalProcedureError> {
+        Ok(Products {
+            secret: self.ty.digest(&guard.borrow()),
+            output: (),
+        })
+    }
+
+    fn source(&self) -> &Location {
+        &self.input
+    }
+
+    fn target(&self) -> (&Location, RecordHint) {
+        (&self.output, self.hint)
+    }
+
+    fn lifetime(&self) -> Option<Duration> {
+        self.lifetime
+    }
+}
+
+/// Targets of the shares that are created by [`ShamirSplit`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ShamirTargets {
+    /// Write each share into its own record.
+    Vault(Vec<(Location, RecordHint)>),
+    /// Return the given number of shares as output.
+    Output(u8),
+}
+
+/// Split the secret at the given [`Location`] into shares with Shamir's Secret Sharing. The secret can be
+/// reconstructed from any `threshold` of the shares with [`ShamirCombine`].
+///
+/// Each share is encoded as its x-coordinate followed by one byte per byte of the secret, which is the format of the
+/// `sharks` crate.  It is not compatible with the mnemonic shares of SLIP-39, so shares can only be combined with
+/// [`ShamirCombine`] or another implementation of this format.
+///
+/// Note: [`ShamirTargets::Output`] exports the shares, and thus the secret, from the vault, and therefore requires
+/// [`ProcedureKind::Copy`] access to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShamirSplit {
+    pub secret: Location,
+
+    pub threshold: u8,
+
+    pub targets: ShamirTargets,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl Procedure for ShamirSplit {
+    type Output = Vec<Vec<u8>>;
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        let count = match &self.targets {
+            ShamirTargets::Vault(locations) => locations.len(),
+            ShamirTargets::Output(count) => *count as usize,
+        };
+        if self.threshold == 0 || count < self.threshold as usize || count > u8::MAX as usize {
+            let e = format!("invalid threshold {} for {} shares", self.threshold, count);
+            return Err(FatalProcedureError::from(e).into());
+        }
+        let threshold = self.threshold;
+        let split = move |guard: GuardedVec<u8>| -> Vec<Vec<u8>> {
+            Sharks(threshold)
+                .dealer(&guard.borrow())
+                .take(count)
+                .map(|share| Vec::from(&share))
+                .collect()
+        };
+        let locations = match self.targets {
+            ShamirTargets::Output(_) => {
+                return runner.get_guard(&self.secret, ProcedureKind::Copy, |guard| Ok(split(guard)))
+            }
+            ShamirTargets::Vault(locations) => locations,
+        };
+
+        // The first share is written through the runner like a derived secret, the remaining ones are written
+        // separately.
+        let (first, hint) = &locations[0];
+        let shares = runner.exec_proc(
+            &self.secret,
+            first,
+            *hint,
+            self.lifetime,
+            ProcedureKind::Derive,
+            |guard| {
+                let mut shares = split(guard);
+                let secret = shares.remove(0);
+                Ok(Products { secret, output: shares })
+            },
+        )?;
+        for (i, ((location, hint), share)) in locations[1..].iter().zip(shares).enumerate() {
+            if let Err(e) = runner.write_to_vault(location, *hint, self.lifetime, share) {
+                for (location, _) in &locations[..=i] {
+                    let _ = runner.revoke_data(location);
+                }
+                return Err(e);
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+/// Reconstruct a secret from the shares at the given [`Location`]s, that were created with [`ShamirSplit`], and
+/// write it into `output`. At least as many shares as the threshold of the split are required, otherwise the
+/// reconstructed secret is invalid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShamirCombine {
+    pub shares: Vec<Location>,
+
+    pub output: Location,
+
+    pub hint: RecordHint,
+
+    pub lifetime: Option<Duration>,
+}
+
+impl ShamirCombine {
+    fn parse_share(bytes: &[u8]) -> Result<Share, FatalProcedureError> {
+        Share::try_from(bytes).map_err(|e| FatalProcedureError::from(e.to_string()))
+    }
+}
+
+impl Procedure for ShamirCombine {
+    type Output = ();
+
+    fn execute<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
+        if self.shares.len() > u8::MAX as usize {
+            let e = format!("too many shares: {}", self.shares.len());
+            return Err(FatalProcedureError::from(e).into());
+        }
+        let (last, rest) = match self.shares.split_last() {
+            Some(split) => split,
+            None => return Err(FatalProcedureError::from("no shares given".to_string()).into()),
+        };
+        let mut shares = Vec::with_capacity(self.shares.len());
+        for location in rest {
+            let share = runner.get_guard(location, ProcedureKind::Derive, |guard| {
+                Self::parse_share(&guard.borrow())
+            })?;
+            shares.push(share);
+        }
+        runner.exec_proc(
+            last,
+            &self.output,
+            self.hint,
+            self.lifetime,
+            ProcedureKind::Derive,
+            |guard| {
+                shares.push(Self::parse_share(&guard.borrow())?);
+                let secret = Sharks(shares.len() as u8).recover(&shares).map_err(|e| e.to_string())?;
+                Ok(Products { secret, output: () })
+            },
+        )
+    }
+}
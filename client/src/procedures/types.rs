@@ -1,41 +1,64 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use super::{CustomProcedure, OtpParameters};
 use crate::{
     actors::{RecordError, VaultError},
-    FatalEngineError, Location,
+    FatalEngineError, Location, PolicyError,
 };
 use engine::{
     runtime::GuardedVec,
     vault::{RecordHint, VaultId},
 };
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, string::FromUtf8Error};
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use thiserror::Error as DeriveError;
+use tokio::sync::Notify;
 
 /// Bridge to the engine that is required for using / writing / revoking secrets in the vault.
 pub trait Runner {
-    fn get_guard<F, T>(&mut self, location0: &Location, f: F) -> Result<T, VaultError<FatalProcedureError>>
+    // Execute a function that uses the secret stored at `location0`. The `kind` is checked against the policy of the
+    // vault at `location0`.
+    fn get_guard<F, T>(&mut self, location0: &Location, kind: ProcedureKind, f: F) -> Result<T, ProcedureError>
     where
         F: FnOnce(GuardedVec<u8>) -> Result<T, FatalProcedureError>;
 
     // Execute a function that uses the secret stored at `location0`. From the returned `Products` the secret is
-    // written into `location1` and the output is returned.
+    // written into `location1` and the output is returned. The `kind` is checked against the policy of the vault
+    // at `location0`. If a `lifetime` is given, the new secret expires after it.
     fn exec_proc<F, T>(
         &mut self,
         location0: &Location,
         location1: &Location,
         hint: RecordHint,
+        lifetime: Option<Duration>,
+        kind: ProcedureKind,
         f: F,
-    ) -> Result<T, VaultError<FatalProcedureError>>
+    ) -> Result<T, ProcedureError>
     where
         F: FnOnce(GuardedVec<u8>) -> Result<Products<T>, FatalProcedureError>;
 
-    fn write_to_vault(&mut self, location1: &Location, hint: RecordHint, value: Vec<u8>) -> Result<(), RecordError>;
+    fn write_to_vault(
+        &mut self,
+        location1: &Location,
+        hint: RecordHint,
+        lifetime: Option<Duration>,
+        value: Vec<u8>,
+    ) -> Result<(), ProcedureError>;
 
     fn revoke_data(&mut self, location: &Location) -> Result<(), RecordError>;
 
     fn garbage_collect(&mut self, vault_id: VaultId) -> bool;
+
+    // Execute a user-defined procedure through the executor that was registered for its name.
+    fn execute_custom(&mut self, procedure: CustomProcedure) -> Result<ProcedureOutput, ProcedureError>;
 }
 
 /// Products of a procedure.
@@ -64,11 +87,17 @@ pub trait GenerateSecret: Sized {
 
     fn target(&self) -> (&Location, RecordHint);
 
+    /// Lifetime of the new secret, after which it is treated as revoked. The secret does not expire by default.
+    fn lifetime(&self) -> Option<Duration> {
+        None
+    }
+
     fn exec<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
         let (target, hint) = self.target();
         let target = target.clone();
+        let lifetime = self.lifetime();
         let Products { output, secret } = self.generate()?;
-        runner.write_to_vault(&target, hint, secret)?;
+        runner.write_to_vault(&target, hint, lifetime, secret)?;
         Ok(output)
     }
 }
@@ -83,12 +112,24 @@ pub trait DeriveSecret: Sized {
 
     fn target(&self) -> (&Location, RecordHint);
 
+    /// Lifetime of the new secret, after which it is treated as revoked. The secret does not expire by default.
+    fn lifetime(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The kind of access to the source secret that is checked against the policy of its vault.
+    fn kind(&self) -> ProcedureKind {
+        ProcedureKind::Derive
+    }
+
     fn exec<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
         let source = self.source().clone();
         let (target, hint) = self.target();
         let target = target.clone();
+        let lifetime = self.lifetime();
+        let kind = self.kind();
         let f = |guard| self.derive(guard);
-        let output = runner.exec_proc(&source, &target, hint, f)?;
+        let output = runner.exec_proc(&source, &target, hint, lifetime, kind, f)?;
         Ok(output)
     }
 }
@@ -104,30 +145,87 @@ pub trait UseSecret: Sized {
     fn exec<R: Runner>(self, runner: &mut R) -> Result<Self::Output, ProcedureError> {
         let source = self.source().clone();
         let f = |guard| self.use_secret(guard);
-        let output = runner.get_guard(&source, f)?;
+        let output = runner.get_guard(&source, ProcedureKind::Use, f)?;
         Ok(output)
     }
 }
 
+/// Kind of access that a procedure has to an existing secret. Vaults may restrict the allowed kinds through
+/// [`VaultFlags::AllowedProcedures`][crate::VaultFlags::AllowedProcedures].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProcedureKind {
+    /// The secret is used for a non-secret output, e.g. a signature or a public key.
+    Use,
+    /// A new secret is derived from the secret.
+    Derive,
+    /// The secret is copied into another record with [`CopyRecord`][super::CopyRecord] or exported from the vault,
+    /// e.g. with [`WrapKey`][super::WrapKey].
+    Copy,
+}
+
 /// Output of a [`StrongholdProcedure`][super::StrongholdProcedure].
+///
+/// The variant corresponds to the output type of the procedure, so that the outputs of a chain can be matched on
+/// instead of being converted blindly. Conversions into a type of another variant fail with [`UnexpectedOutput`].
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct ProcedureOutput(Vec<u8>);
+pub enum ProcedureOutput {
+    /// The procedure has no output.
+    Empty,
+    /// Bytes, e.g. a public key, signature or ciphertext.
+    Bytes(Vec<u8>),
+    /// Text, e.g. a mnemonic, one-time password or JWS.
+    String(String),
+    /// List of byte vectors, e.g. the shares of [`ShamirSplit`][super::ShamirSplit].
+    List(Vec<Vec<u8>>),
+    /// Result of a verification.
+    Bool(bool),
+    /// Parameters of an `otpauth` URI that was imported with [`ImportOtpauth`][super::ImportOtpauth].
+    OtpParameters(OtpParameters),
+}
+
+impl ProcedureOutput {
+    /// Name of the variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProcedureOutput::Empty => "Empty",
+            ProcedureOutput::Bytes(_) => "Bytes",
+            ProcedureOutput::String(_) => "String",
+            ProcedureOutput::List(_) => "List",
+            ProcedureOutput::Bool(_) => "Bool",
+            ProcedureOutput::OtpParameters(_) => "OtpParameters",
+        }
+    }
+
+    /// Raw bytes of the output, as they are passed to the input of another procedure with a
+    /// [`ChainInput`][super::ChainInput]. Outputs that are not bytes or text are serialized with `bincode`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            ProcedureOutput::Empty => Vec::new(),
+            ProcedureOutput::Bytes(v) => v.clone(),
+            ProcedureOutput::String(s) => s.as_bytes().to_vec(),
+            ProcedureOutput::Bool(b) => vec![*b as u8],
+            // Serializing plain data types into a vector can not fail.
+            ProcedureOutput::List(v) => bincode::serialize(v).unwrap_or_default(),
+            ProcedureOutput::OtpParameters(params) => bincode::serialize(params).unwrap_or_default(),
+        }
+    }
+}
 
 impl From<()> for ProcedureOutput {
     fn from(_: ()) -> Self {
-        ProcedureOutput(Vec::new())
+        ProcedureOutput::Empty
     }
 }
 
 impl From<Vec<u8>> for ProcedureOutput {
     fn from(v: Vec<u8>) -> Self {
-        ProcedureOutput(v)
+        ProcedureOutput::Bytes(v)
     }
 }
 
 impl From<String> for ProcedureOutput {
     fn from(s: String) -> Self {
-        s.into_bytes().into()
+        ProcedureOutput::String(s)
     }
 }
 
@@ -137,28 +235,152 @@ impl<const N: usize> From<[u8; N]> for ProcedureOutput {
     }
 }
 
+impl From<Vec<Vec<u8>>> for ProcedureOutput {
+    fn from(v: Vec<Vec<u8>>) -> Self {
+        ProcedureOutput::List(v)
+    }
+}
+
+impl From<bool> for ProcedureOutput {
+    fn from(b: bool) -> Self {
+        ProcedureOutput::Bool(b)
+    }
+}
+
+impl From<OtpParameters> for ProcedureOutput {
+    fn from(params: OtpParameters) -> Self {
+        ProcedureOutput::OtpParameters(params)
+    }
+}
+
 impl From<ProcedureOutput> for () {
     fn from(_: ProcedureOutput) -> Self {}
 }
 
-impl From<ProcedureOutput> for Vec<u8> {
-    fn from(value: ProcedureOutput) -> Self {
-        value.0
+impl TryFrom<ProcedureOutput> for Vec<u8> {
+    type Error = UnexpectedOutput;
+
+    fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
+        match value {
+            ProcedureOutput::Bytes(v) => Ok(v),
+            other => Err(UnexpectedOutput(other)),
+        }
     }
 }
 
 impl TryFrom<ProcedureOutput> for String {
-    type Error = FromUtf8Error;
+    type Error = UnexpectedOutput;
+
+    fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
+        match value {
+            ProcedureOutput::String(s) => Ok(s),
+            other => Err(UnexpectedOutput(other)),
+        }
+    }
+}
+
+impl TryFrom<ProcedureOutput> for Vec<Vec<u8>> {
+    type Error = UnexpectedOutput;
+
     fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
-        String::from_utf8(value.0)
+        match value {
+            ProcedureOutput::List(v) => Ok(v),
+            other => Err(UnexpectedOutput(other)),
+        }
+    }
+}
+
+impl TryFrom<ProcedureOutput> for bool {
+    type Error = UnexpectedOutput;
+
+    fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
+        match value {
+            ProcedureOutput::Bool(b) => Ok(b),
+            other => Err(UnexpectedOutput(other)),
+        }
+    }
+}
+
+impl TryFrom<ProcedureOutput> for OtpParameters {
+    type Error = UnexpectedOutput;
+
+    fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
+        match value {
+            ProcedureOutput::OtpParameters(params) => Ok(params),
+            other => Err(UnexpectedOutput(other)),
+        }
     }
 }
 
 impl<const N: usize> TryFrom<ProcedureOutput> for [u8; N] {
-    type Error = <[u8; N] as TryFrom<Vec<u8>>>::Error;
+    type Error = UnexpectedOutput;
 
     fn try_from(value: ProcedureOutput) -> Result<Self, Self::Error> {
-        value.0.try_into()
+        match value {
+            ProcedureOutput::Bytes(v) => v.try_into().map_err(|v| UnexpectedOutput(ProcedureOutput::Bytes(v))),
+            other => Err(UnexpectedOutput(other)),
+        }
+    }
+}
+
+/// Error on converting a [`ProcedureOutput`] into the type of another variant, or into an array of another length.
+/// Contains the output that could not be converted.
+#[derive(DeriveError, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[error("unexpected procedure output {}", .0.kind())]
+pub struct UnexpectedOutput(pub ProcedureOutput);
+
+/// Options for the execution of procedures with
+/// [`Stronghold::runtime_exec_with_options`][crate::Stronghold::runtime_exec_with_options].
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionOptions {
+    /// Duration after which the execution is cancelled.  A procedure that is running at that point, e.g. a key
+    /// derivation with a high iteration count, still runs to its end in the background, but its result is discarded.
+    pub timeout: Option<Duration>,
+
+    /// Token to cancel the execution from another task.
+    pub cancellation: Option<CancellationToken>,
+}
+
+/// Token to cancel the execution of procedures.
+///
+/// Procedures are executed on a blocking thread and a running procedure can not be interrupted, so a cancelled chain
+/// stops before its next procedure. The caller is notified right away and none of the changes of the chain are
+/// applied.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<CancellationState>);
+
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the execution. Has no effect if the execution has already finished.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token was cancelled.
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            // A `Notified` future receives all notifications after its creation, so a cancellation between the check
+            // and the await is not missed.
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
     }
 }
 
@@ -172,6 +394,37 @@ pub enum ProcedureError {
     /// Operation on the vault failed.
     #[error("procedure: {0}")]
     Procedure(#[from] FatalProcedureError),
+
+    /// Operation was denied by the policy of the vault.
+    #[error("policy: {0}")]
+    Policy(#[from] PolicyError),
+
+    /// The procedure at `index` of a chain failed. None of the changes of the chain have been applied.
+    #[error("procedure {index} of the chain failed: {error}")]
+    Chain { index: usize, error: Box<ProcedureError> },
+
+    /// The execution was cancelled through its [`CancellationToken`]. None of the changes have been applied.
+    #[error("procedure execution was cancelled")]
+    Cancelled,
+
+    /// The execution did not finish within its timeout. None of the changes have been applied.
+    #[error("procedure execution timed out")]
+    TimedOut,
+
+    /// A vault that the procedures changed was also changed by another operation during their execution. None of the
+    /// changes have been applied.
+    #[error("vault was changed during procedure execution")]
+    Conflict,
+}
+
+impl ProcedureError {
+    /// Returns the error of the failed procedure of a [`ProcedureError::Chain`].
+    pub fn into_inner(self) -> ProcedureError {
+        match self {
+            ProcedureError::Chain { error, .. } => *error,
+            other => other,
+        }
+    }
 }
 
 impl<T> From<VaultError<T>> for ProcedureError
@@ -211,14 +464,14 @@ impl From<String> for FatalProcedureError {
 
 #[cfg(test)]
 mod test {
-    use super::ProcedureOutput;
+    use super::{ProcedureOutput, UnexpectedOutput};
     use stronghold_utils::random;
 
     #[test]
     fn proc_io_vec() {
         let vec = random::bytestring(2048);
         let proc_io: ProcedureOutput = vec.clone().into();
-        let converted = Vec::try_from(proc_io).unwrap();
+        let converted = Vec::<u8>::try_from(proc_io).unwrap();
         assert_eq!(vec.len(), converted.len());
         assert_eq!(vec, converted);
     }
@@ -243,4 +496,23 @@ mod test {
         let converted = <[u8; 337]>::try_from(proc_io).unwrap();
         assert_eq!(array, converted);
     }
+
+    #[test]
+    fn proc_io_mismatch() {
+        let proc_io: ProcedureOutput = random::bytestring(32).into();
+        assert_eq!(
+            String::try_from(proc_io.clone()),
+            Err(UnexpectedOutput(proc_io.clone()))
+        );
+        assert_eq!(bool::try_from(proc_io.clone()), Err(UnexpectedOutput(proc_io.clone())));
+        assert_eq!(<[u8; 64]>::try_from(proc_io.clone()), Err(UnexpectedOutput(proc_io)));
+
+        let proc_io: ProcedureOutput = true.into();
+        assert_eq!(proc_io, ProcedureOutput::Bool(true));
+        assert_eq!(
+            Vec::<u8>::try_from(proc_io.clone()),
+            Err(UnexpectedOutput(proc_io.clone()))
+        );
+        assert_eq!(proc_io.to_bytes(), vec![1]);
+    }
 }
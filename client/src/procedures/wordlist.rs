@@ -0,0 +1,16 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! BIP39 wordlists of the languages that are not shipped with `iota-crypto`, which only provides English and
+//! Japanese.
+
+use crypto::keys::bip39::wordlist::Wordlist;
+
+include!("wordlist/bip39.cs.rs");
+include!("wordlist/bip39.es.rs");
+include!("wordlist/bip39.fr.rs");
+include!("wordlist/bip39.it.rs");
+include!("wordlist/bip39.ko.rs");
+include!("wordlist/bip39.pt.rs");
+include!("wordlist/bip39.zh-hans.rs");
+include!("wordlist/bip39.zh-hant.rs");
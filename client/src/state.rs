@@ -1,6 +1,7 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod audit;
 pub mod key_store;
 #[cfg(feature = "p2p")]
 pub mod p2p;
@@ -0,0 +1,238 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Audit Log of the operations on a Client
+
+use crate::Location;
+use crypto::macs::hmac::HMAC_SHA256;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error as DeriveError;
+
+/// Length of the hash that links an [`AuditEntry`] to its predecessor.
+pub const AUDIT_HASH_LEN: usize = 32;
+
+/// A procedure that was executed as part of an [`AuditOperation::Procedures`] call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditProcedure {
+    /// Name of the procedure.
+    pub name: String,
+    /// Location of the secret that the procedure used, if any.
+    pub input: Option<Location>,
+    /// Location of the secret that the procedure wrote, if any.
+    pub output: Option<Location>,
+}
+
+/// Operation on a client that is recorded in the [`AuditLog`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditOperation {
+    /// Execution of a chain of procedures.
+    Procedures(Vec<AuditProcedure>),
+    /// Write of a record into a vault.
+    Write(Location),
+    /// Revocation of a record.
+    Revoke(Location),
+    /// Garbage collection of the vault at the given path.
+    GarbageCollect(Vec<u8>),
+    /// Write of the given key into the store.
+    StoreWrite(Vec<u8>),
+    /// Read of the given key from the store.
+    StoreRead(Vec<u8>),
+    /// Deletion of the given key from the store.
+    StoreDelete(Vec<u8>),
+    /// Rotation of the key of the vault at the given path.
+    RotateVaultKey(Vec<u8>),
+    /// Removal of all vaults, records and the store of the client.
+    ClearCache,
+    /// Replacement of the vaults, records and the store of the client with the ones of a snapshot.
+    LoadSnapshot,
+}
+
+impl AuditOperation {
+    /// Checks if the operation accessed the vault at the given path.
+    pub fn touches_vault(&self, vault_path: &[u8]) -> bool {
+        let is_vault = |location: &Location| location.vault_path() == vault_path;
+        match self {
+            AuditOperation::Procedures(procs) => procs
+                .iter()
+                .any(|p| p.input.iter().chain(p.output.iter()).any(is_vault)),
+            AuditOperation::Write(location) | AuditOperation::Revoke(location) => is_vault(location),
+            AuditOperation::GarbageCollect(path) | AuditOperation::RotateVaultKey(path) => path == vault_path,
+            _ => false,
+        }
+    }
+}
+
+/// Outcome of an audited operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditOutcome {
+    Success,
+    Failure(String),
+}
+
+impl<T, E: ToString> From<&Result<T, E>> for AuditOutcome {
+    fn from(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => AuditOutcome::Success,
+            Err(e) => AuditOutcome::Failure(e.to_string()),
+        }
+    }
+}
+
+/// A single entry of the [`AuditLog`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position of the entry in the log.
+    pub index: u64,
+    /// Time of the operation in seconds since the unix epoch.
+    pub timestamp: u64,
+    /// The audited operation.
+    pub operation: AuditOperation,
+    /// Outcome of the operation.
+    pub outcome: AuditOutcome,
+    /// Base58 encoded `PeerId` of the remote peer that issued the request, `None` for local requests.
+    pub peer: Option<String>,
+    /// Hash of the previous entry, all zeros for the first entry.
+    pub prev_hash: [u8; AUDIT_HASH_LEN],
+    /// HMAC-SHA-256 under the key of the log over the previous hash and the content of this entry.
+    pub hash: [u8; AUDIT_HASH_LEN],
+}
+
+impl AuditEntry {
+    /// Computes the hash of the entry from its content and `prev_hash`.
+    fn compute_hash(&self, key: &[u8]) -> [u8; AUDIT_HASH_LEN] {
+        // Serializing plain data types into a vector can not fail.
+        let content = bincode::serialize(&(self.index, self.timestamp, &self.operation, &self.outcome, &self.peer))
+            .unwrap_or_default();
+        let mut hash = [0u8; AUDIT_HASH_LEN];
+        HMAC_SHA256(&[&self.prev_hash[..], &content].concat(), key, &mut hash);
+        hash
+    }
+}
+
+/// Filter for the entries of the [`AuditLog`]. All set conditions have to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditQuery {
+    /// Only entries with a timestamp at or after this unix time.
+    pub since: Option<u64>,
+    /// Only entries with a timestamp before this unix time.
+    pub until: Option<u64>,
+    /// Only entries of operations that accessed the vault at this path.
+    pub vault_path: Option<Vec<u8>>,
+    /// Only entries of requests from the remote peer with this base58 encoded `PeerId`.
+    pub peer: Option<String>,
+    /// Only entries of failed operations.
+    pub failures_only: bool,
+}
+
+impl AuditQuery {
+    /// Checks if the entry matches the query.
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp < until)
+            && self
+                .vault_path
+                .as_ref()
+                .is_none_or(|path| entry.operation.touches_vault(path))
+            && self.peer.as_ref().is_none_or(|peer| entry.peer.as_ref() == Some(peer))
+            && (!self.failures_only || matches!(entry.outcome, AuditOutcome::Failure(_)))
+    }
+}
+
+#[derive(Debug, DeriveError, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditError {
+    #[error("audit log entry {0} has an invalid index")]
+    InvalidIndex(u64),
+
+    #[error("audit log entry {0} is not linked to its predecessor")]
+    BrokenChain(u64),
+
+    #[error("audit log entry {0} has been modified")]
+    InvalidHash(u64),
+}
+
+/// Append-only log of the operations on a client. Each entry is linked to its predecessor through its hash, so that
+/// modifications of past entries can be detected with [`AuditLog::verify`].
+///
+/// The hashes are keyed with a random key that the client holds in its keystore under
+/// [`AUDIT_VAULT_PATH`][crate::AUDIT_VAULT_PATH], next to the keys of its vaults.  Entries can therefore not be forged
+/// by someone who is able to modify the log, but does not have access to the key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditLog {
+    pub(crate) entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Appends a new entry for the operation to the log, whose hash is keyed with `key`.
+    pub fn append(&mut self, key: &[u8], operation: AuditOperation, outcome: AuditOutcome, peer: Option<String>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = AuditEntry {
+            index: 0,
+            timestamp,
+            operation,
+            outcome,
+            peer,
+            prev_hash: [0u8; AUDIT_HASH_LEN],
+            hash: [0u8; AUDIT_HASH_LEN],
+        };
+        self.push(key, entry);
+    }
+
+    /// Appends the entries of `other` that follow the entries that both logs have in common.  They keep their
+    /// timestamps, but are chained onto this log and keyed with `key`.
+    pub fn merge(&mut self, key: &[u8], other: AuditLog) {
+        let common = self
+            .entries
+            .iter()
+            .zip(&other.entries)
+            .take_while(|(entry, other)| entry.hash == other.hash)
+            .count();
+        for entry in other.entries.into_iter().skip(common) {
+            self.push(key, entry);
+        }
+    }
+
+    fn push(&mut self, key: &[u8], mut entry: AuditEntry) {
+        entry.index = self.entries.len() as u64;
+        entry.prev_hash = self.head();
+        entry.hash = entry.compute_hash(key);
+        self.entries.push(entry);
+    }
+
+    /// Hash of the latest entry, all zeros if the log is empty.
+    pub fn head(&self) -> [u8; AUDIT_HASH_LEN] {
+        self.entries.last().map(|e| e.hash).unwrap_or([0u8; AUDIT_HASH_LEN])
+    }
+
+    /// All entries of the log in the order in which they were appended.
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Returns the entries that match the query.
+    pub fn query(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        self.entries.iter().filter(|e| query.matches(e)).cloned().collect()
+    }
+
+    /// Verifies the hash chain of the log under `key` and returns the hash of the latest entry. Fails at the first
+    /// entry that was modified, removed or inserted.
+    pub fn verify(&self, key: &[u8]) -> Result<[u8; AUDIT_HASH_LEN], AuditError> {
+        let mut prev_hash = [0u8; AUDIT_HASH_LEN];
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.index != i as u64 {
+                return Err(AuditError::InvalidIndex(i as u64));
+            }
+            if entry.prev_hash != prev_hash {
+                return Err(AuditError::BrokenChain(entry.index));
+            }
+            if entry.compute_hash(key) != entry.hash {
+                return Err(AuditError::InvalidHash(entry.index));
+            }
+            prev_hash = entry.hash;
+        }
+        Ok(prev_hash)
+    }
+}
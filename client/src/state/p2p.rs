@@ -11,7 +11,7 @@ use crate::{
     },
     enum_from_inner,
     procedures::{self, ProcedureError, ProcedureOutput, StrongholdProcedure},
-    Location, RecordHint, RecordId,
+    Location, RecordHint, RecordInfo, VaultFlags,
 };
 use actix::prelude::*;
 use futures::{
@@ -713,6 +713,43 @@ impl FwRequest<ShRequest> for AccessRequest {
                             vault_path: vault_path.clone(),
                         }]
                     }
+                    StrongholdProcedure::ShamirSplit(procedures::ShamirSplit { secret, targets, .. }) => {
+                        let vault_path = secret.vault_path().to_vec();
+                        match targets {
+                            // The shares of the secret are returned to the remote.
+                            procedures::ShamirTargets::Output(_) => vec![Access::Clone { vault_path }],
+                            procedures::ShamirTargets::Vault(locations) => {
+                                let mut access = vec![Access::Use { vault_path }];
+                                access.extend(locations.iter().map(|(location, _)| Access::Write {
+                                    vault_path: location.vault_path().to_vec(),
+                                }));
+                                access
+                            }
+                        }
+                    }
+                    StrongholdProcedure::WrapKey(procedures::WrapKey { wrapping_key, key, .. }) => {
+                        vec![
+                            Access::Use {
+                                vault_path: wrapping_key.vault_path().to_vec(),
+                            },
+                            // The wrapped key is returned to the remote.
+                            Access::Clone {
+                                vault_path: key.vault_path().to_vec(),
+                            },
+                        ]
+                    }
+                    StrongholdProcedure::ShamirCombine(procedures::ShamirCombine { shares, output, .. }) => {
+                        let mut access: Vec<_> = shares
+                            .iter()
+                            .map(|location| Access::Use {
+                                vault_path: location.vault_path().to_vec(),
+                            })
+                            .collect();
+                        access.push(Access::Write {
+                            vault_path: output.vault_path().to_vec(),
+                        });
+                        access
+                    }
                     proc => {
                         let mut access = Vec::new();
                         if let Some(input) = proc.input() {
@@ -743,6 +780,7 @@ pub struct WriteToRemoteVault {
     pub location: Location,
     pub payload: Vec<u8>,
     pub hint: RecordHint,
+    pub options: Vec<VaultFlags>,
 }
 
 impl From<WriteToRemoteVault> for WriteToVault {
@@ -751,11 +789,13 @@ impl From<WriteToRemoteVault> for WriteToVault {
             location,
             payload,
             hint,
+            options,
         } = t;
         WriteToVault {
             location,
             payload,
             hint,
+            options,
         }
     }
 }
@@ -766,11 +806,13 @@ impl From<WriteToVault> for WriteToRemoteVault {
             location,
             payload,
             hint,
+            options,
         } = t;
         WriteToRemoteVault {
             location,
             payload,
             hint,
+            options,
         }
     }
 }
@@ -816,14 +858,14 @@ pub enum ShResult {
     Data(Option<Vec<u8>>),
     Bool(bool),
     WriteRemoteVault(Result<(), RemoteRecordError>),
-    ListIds(Vec<(RecordId, RecordHint)>),
+    ListIds(Vec<RecordInfo>),
     Proc(Result<Vec<ProcedureOutput>, ProcedureError>),
 }
 
 sh_result_mapping!(ShResult::Empty => ());
 sh_result_mapping!(ShResult::Bool => bool);
 sh_result_mapping!(ShResult::Data => Option<Vec<u8>>);
-sh_result_mapping!(ShResult::ListIds => Vec<(RecordId, RecordHint)>);
+sh_result_mapping!(ShResult::ListIds => Vec<RecordInfo>);
 sh_result_mapping!(ShResult::Proc => Result<Vec<ProcedureOutput>, ProcedureError>);
 
 impl From<Result<(), RecordError>> for ShResult {
@@ -832,6 +874,12 @@ impl From<Result<(), RecordError>> for ShResult {
     }
 }
 
+impl From<Result<(), ProcedureError>> for ShResult {
+    fn from(inner: Result<(), ProcedureError>) -> Self {
+        ShResult::WriteRemoteVault(inner.map_err(|e| e.to_string()))
+    }
+}
+
 impl TryFrom<ShResult> for Result<(), RemoteRecordError> {
     type Error = ();
     fn try_from(t: ShResult) -> Result<Self, Self::Error> {
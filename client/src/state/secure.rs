@@ -6,17 +6,27 @@
 use crate::{
     actors::{RecordError, VaultError},
     internals,
-    procedures::{FatalProcedureError, Products, Runner},
-    state::key_store::KeyStore,
+    procedures::{
+        CancellationToken, ChainInput, CustomProcedure, FatalProcedureError, Procedure, ProcedureError, ProcedureKind,
+        ProcedureOutput, ProcedureRegistry, Products, Runner, StrongholdProcedure,
+    },
+    state::{
+        audit::{AuditError, AuditLog, AuditOperation, AuditOutcome, AUDIT_HASH_LEN},
+        key_store::KeyStore,
+    },
     utils::LoadFromPath,
-    Location,
+    Location, PolicyError, RecordInfo, RecordMetadata, VaultFlags, VaultPolicy, AUDIT_VAULT_PATH, EPHEMERAL_VAULT_PATH,
 };
 use engine::{
     runtime::GuardedVec,
     store::Cache,
-    vault::{ClientId, DbView, RecordHint, RecordId, VaultId},
+    vault::{ClientId, DbView, Key, RecordHint, RecordId, VaultError as EngineVaultError, VaultId},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use std::time::Duration;
 
 /// Cache type definition
 pub type Store = Cache<Vec<u8>, Vec<u8>>;
@@ -30,6 +40,72 @@ pub struct SecureClient {
     pub client_id: ClientId,
     // Contains the Record Ids for the most recent Record in each vault.
     pub store: Store,
+    // State of the vaults that is kept next to the `DbView`.
+    pub(crate) client_state: ClientState,
+    // Base58 encoded `PeerId` of the remote peer whose request is currently handled.
+    pub(crate) remote_peer: Option<String>,
+    // User-defined procedures that were registered with the Stronghold.
+    pub(crate) custom_procedures: ProcedureRegistry,
+    // Revisions of the vaults, to detect changes while procedures are executed on a staging copy.
+    pub(crate) revisions: Revisions,
+}
+
+/// Revisions of the vaults of a client, which are increased on every change of a vault.  Recording the last use of a
+/// record in its metadata does not count as a change.
+#[derive(Default)]
+pub(crate) struct Revisions {
+    counter: u64,
+    reset: u64,
+    vaults: HashMap<VaultId, u64>,
+    // Records whose last use was recorded.
+    used: HashSet<(VaultId, RecordId)>,
+}
+
+impl Revisions {
+    fn get(&self, vault_id: VaultId) -> u64 {
+        self.vaults.get(&vault_id).copied().unwrap_or(self.reset)
+    }
+
+    fn changed(&self, vault_id: VaultId) -> bool {
+        self.vaults.contains_key(&vault_id)
+    }
+
+    fn bump(&mut self, vault_id: VaultId) {
+        self.counter += 1;
+        self.vaults.insert(vault_id, self.counter);
+    }
+
+    /// Changes the revisions of all vaults, after the vaults were cleared or replaced.
+    pub(crate) fn reset(&mut self) {
+        self.counter += 1;
+        self.reset = self.counter;
+        self.vaults.clear();
+        self.used.clear();
+    }
+}
+
+/// Copy of the vaults that procedures access, on which they are executed outside of the actor.
+pub(crate) struct Staging {
+    pub(crate) client: SecureClient,
+    // Revisions of the copied vaults at the time of the copy.
+    revisions: HashMap<VaultId, u64>,
+}
+
+/// State of the vaults of a client that is kept outside of the engine and persisted in snapshots.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ClientState {
+    /// Policies of the vaults that were created with [`VaultFlags`].
+    pub policies: HashMap<VaultId, VaultPolicy>,
+
+    /// Expiration times of records that were written with a lifetime.
+    pub expirations: HashMap<VaultId, HashMap<RecordId, SystemTime>>,
+
+    /// Records that were written to write-once vaults.  They are kept after the record was revoked and garbage
+    /// collected, so that the location can not be written again.
+    pub written_once: HashMap<VaultId, HashSet<RecordId>>,
+
+    /// Audit log of the operations on the client.
+    pub audit_log: AuditLog,
 }
 
 impl SecureClient {
@@ -42,9 +118,68 @@ impl SecureClient {
             store,
             keystore: KeyStore::new(),
             db: DbView::new(),
+            client_state: ClientState::default(),
+            remote_peer: None,
+            custom_procedures: ProcedureRegistry::default(),
+            revisions: Revisions::default(),
         }
     }
 
+    /// Appends an entry for the operation to the audit log, together with the peer of the current remote request.
+    pub fn audit<O: Into<AuditOutcome>>(&mut self, operation: AuditOperation, outcome: O) {
+        let peer = self.remote_peer.clone();
+        self.append_audit_entry(operation, outcome.into(), peer);
+    }
+
+    /// Appends an entry for the operation to the audit log.  The key of the log is created on its first entry.
+    pub(crate) fn append_audit_entry(
+        &mut self,
+        operation: AuditOperation,
+        outcome: AuditOutcome,
+        peer: Option<String>,
+    ) {
+        let key = self
+            .keystore
+            .create_key(Self::derive_vault_id(AUDIT_VAULT_PATH.to_vec()));
+        self.client_state
+            .audit_log
+            .append(&key.key.borrow(), operation, outcome, peer);
+    }
+
+    /// Verifies the audit log under its key and returns the hash of the latest entry.
+    pub fn verify_audit_log(&mut self) -> Result<[u8; AUDIT_HASH_LEN], AuditError> {
+        let key = self
+            .keystore
+            .create_key(Self::derive_vault_id(AUDIT_VAULT_PATH.to_vec()));
+        self.client_state.audit_log.verify(&key.key.borrow())
+    }
+
+    /// Takes the audit log and its key out of the client, so that they can be kept while the rest of its state is
+    /// replaced.
+    pub(crate) fn take_audit_log(&mut self) -> (AuditLog, Option<Key<internals::Provider>>) {
+        let key = self.keystore.take_key(Self::derive_vault_id(AUDIT_VAULT_PATH.to_vec()));
+        (std::mem::take(&mut self.client_state.audit_log), key)
+    }
+
+    /// Replaces the audit log and its key with the ones that were taken with [`SecureClient::take_audit_log`].
+    pub(crate) fn restore_audit_log(&mut self, audit_log: AuditLog, key: Option<Key<internals::Provider>>) {
+        let vault_id = Self::derive_vault_id(AUDIT_VAULT_PATH.to_vec());
+        self.keystore.take_key(vault_id);
+        if let Some(key) = key {
+            self.keystore.insert_key(vault_id, key);
+        }
+        self.client_state.audit_log = audit_log;
+    }
+
+    /// Appends the entries of another audit log of the client that are not part of its log, e.g. the ones that were
+    /// recorded before a snapshot was loaded.  They are chained onto the log and keyed with its key.
+    pub(crate) fn merge_audit_log(&mut self, audit_log: AuditLog) {
+        let key = self
+            .keystore
+            .create_key(Self::derive_vault_id(AUDIT_VAULT_PATH.to_vec()));
+        self.client_state.audit_log.merge(&key.key.borrow(), audit_log);
+    }
+
     /// Write unencrypted data to the store.  Returns [`None`] if the key didn't already exist and [`Some(Vec<u8>)`] if
     /// the key was updated.
     pub fn write_to_store(&mut self, key: Vec<u8>, data: Vec<u8>, lifetime: Option<Duration>) -> Option<Vec<u8>> {
@@ -78,6 +213,360 @@ impl SecureClient {
         self.store = store;
     }
 
+    /// Sets the policy for the vault with the given [`VaultId`] if the vault does not exist yet.  Policies of existing
+    /// vaults can not be changed.
+    pub fn init_vault_policy(&mut self, vault_id: VaultId, flags: Vec<VaultFlags>) {
+        if flags.is_empty() || self.keystore.vault_exists(vault_id) {
+            return;
+        }
+        self.client_state.policies.insert(vault_id, VaultPolicy::new(flags));
+        self.revisions.bump(vault_id);
+    }
+
+    /// Checks if procedures of the given kind may access the records of the vault.
+    fn check_policy_kind(&self, vault_id: VaultId, kind: ProcedureKind) -> Result<(), PolicyError> {
+        match self.client_state.policies.get(&vault_id) {
+            Some(policy) => policy.check_kind(kind),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks if the record may be written according to the policy of its vault.
+    fn check_policy_write(&mut self, vault_id: VaultId, record_id: RecordId) -> Result<(), PolicyError> {
+        let policy = match self.client_state.policies.get(&vault_id) {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return policy.check_write(false, 0),
+        };
+        let written = match self.client_state.written_once.get(&vault_id) {
+            Some(records) => records.contains(&record_id),
+            None => false,
+        };
+        let exists = written || self.db.contains_record(&key, vault_id, record_id);
+        let count = self.db.list_hints_and_ids(&key, vault_id).len();
+        self.keystore.insert_key(vault_id, key);
+        policy.check_write(exists, count)
+    }
+
+    /// Remembers that the record was written if its vault is write-once.
+    fn mark_written(&mut self, vault_id: VaultId, record_id: RecordId) {
+        if let Some(VaultPolicy { write_once: true, .. }) = self.client_state.policies.get(&vault_id) {
+            self.client_state
+                .written_once
+                .entry(vault_id)
+                .or_default()
+                .insert(record_id);
+        }
+    }
+
+    /// Sets the expiration time of a record that was written with the given lifetime.
+    fn set_lifetime(&mut self, vault_id: VaultId, record_id: RecordId, lifetime: Option<Duration>) {
+        let expirations = &mut self.client_state.expirations;
+        match lifetime {
+            Some(lifetime) => {
+                expirations
+                    .entry(vault_id)
+                    .or_default()
+                    .insert(record_id, SystemTime::now() + lifetime);
+            }
+            None => {
+                if let Some(records) = expirations.get_mut(&vault_id) {
+                    records.remove(&record_id);
+                }
+            }
+        }
+    }
+
+    /// Revokes all records of the vault whose lifetime has passed.
+    fn revoke_expired(&mut self, vault_id: VaultId) {
+        let now = SystemTime::now();
+        let expired: Vec<RecordId> = match self.client_state.expirations.get_mut(&vault_id) {
+            Some(records) => {
+                let expired = records
+                    .iter()
+                    .filter(|(_, expiration)| now >= **expiration)
+                    .map(|(record_id, _)| *record_id)
+                    .collect();
+                records.retain(|_, expiration| now < *expiration);
+                expired
+            }
+            None => return,
+        };
+        if expired.is_empty() {
+            return;
+        }
+        if let Some(key) = self.keystore.take_key(vault_id) {
+            for record_id in expired {
+                let _ = self.db.revoke_record(&key, vault_id, record_id);
+            }
+            self.keystore.insert_key(vault_id, key);
+            self.revisions.bump(vault_id);
+        }
+    }
+
+    /// Reads the metadata of a record. Returns `None` if the record does not exist or has no metadata.
+    pub fn read_metadata(&mut self, vault_id: VaultId, record_id: RecordId) -> Option<RecordMetadata> {
+        let key = self.keystore.take_key(vault_id)?;
+        let res = self.db.get_metadata(&key, vault_id, record_id);
+        self.keystore.insert_key(vault_id, key);
+        let bytes = res.ok()??;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    /// Encrypts and stores the metadata of a record.
+    fn write_metadata(
+        &mut self,
+        vault_id: VaultId,
+        record_id: RecordId,
+        metadata: &RecordMetadata,
+    ) -> Result<(), RecordError> {
+        let bytes = bincode::serialize(metadata).map_err(|e| RecordError::CorruptedContent(e.to_string()))?;
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let res = self.db.set_metadata(&key, vault_id, record_id, &bytes);
+        self.keystore.insert_key(vault_id, key);
+        match res {
+            Ok(()) | Err(EngineVaultError::VaultNotFound(_)) => Ok(()),
+            Err(EngineVaultError::Record(e)) => Err(e),
+            Err(EngineVaultError::Procedure(e)) => match e {},
+        }
+    }
+
+    /// Records the current time as the last use of the record.
+    fn touch_metadata(&mut self, vault_id: VaultId, record_id: RecordId) -> Result<(), RecordError> {
+        match self.read_metadata(vault_id, record_id) {
+            Some(mut metadata) => {
+                metadata.last_used = Some(unix_time());
+                self.write_metadata(vault_id, record_id, &metadata)?;
+                self.revisions.used.insert((vault_id, record_id));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Sets the name of the procedure that created the record at the given location.
+    pub fn set_metadata_origin(&mut self, location: &Location, origin: &str) -> Result<(), RecordError> {
+        let (vault_id, record_id) = Self::resolve_location(location);
+        match self.read_metadata(vault_id, record_id) {
+            Some(mut metadata) => {
+                metadata.origin = Some(origin.into());
+                self.write_metadata(vault_id, record_id, &metadata)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Lists the live records of the vault with their metadata.
+    pub fn list_records(&mut self, vault_id: VaultId) -> Vec<RecordInfo> {
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Vec::new(),
+        };
+        let list = self.db.list_hints_and_ids(&key, vault_id);
+        self.keystore.insert_key(vault_id, key);
+
+        list.into_iter()
+            .map(|(id, hint)| RecordInfo {
+                id,
+                hint,
+                metadata: self.read_metadata(vault_id, id),
+            })
+            .collect()
+    }
+
+    /// Executes the procedures sequentially, passing outputs to later procedures as specified by the `inputs`.
+    ///
+    /// The chain is executed on a [`staging`](Self::staging) copy of the vaults that it accesses, which is only
+    /// committed if all procedures succeeded.  Otherwise the copy is discarded with all writes and revocations of the
+    /// chain, and a [`ProcedureError::Chain`] with the index of the failed procedure is returned.  Records at ephemeral
+    /// locations are removed in either case.  Once `cancellation` was cancelled, the chain stops before its next
+    /// procedure and fails with [`ProcedureError::Cancelled`].
+    pub(crate) fn execute_procedures(
+        &mut self,
+        procedures: Vec<StrongholdProcedure>,
+        inputs: Vec<ChainInput>,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<ProcedureOutput>, ProcedureError> {
+        let res = self.execute_chain(procedures, inputs, cancellation);
+        self.clear_ephemeral();
+        res
+    }
+
+    fn execute_chain(
+        &mut self,
+        procedures: Vec<StrongholdProcedure>,
+        inputs: Vec<ChainInput>,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<ProcedureOutput>, ProcedureError> {
+        if let Some(input) = inputs
+            .iter()
+            .find(|input| input.source >= input.target || input.target >= procedures.len())
+        {
+            let e = format!("invalid input from procedure {}", input.source);
+            return Err(ProcedureError::Chain {
+                index: input.target,
+                error: Box::new(FatalProcedureError::from(e).into()),
+            });
+        }
+
+        let mut out: Vec<ProcedureOutput> = Vec::new();
+        for (index, mut proc) in procedures.into_iter().enumerate() {
+            if cancellation.is_cancelled() {
+                return Err(ProcedureError::Cancelled);
+            }
+            let target = proc.output();
+            let name = proc.name();
+            let bound = inputs
+                .iter()
+                .filter(|input| input.target == index)
+                .try_for_each(|input| proc.set_input(input.slot, out[input.source].to_bytes()));
+            let res = bound
+                .map_err(ProcedureError::from)
+                .and_then(|()| proc.execute(self))
+                .and_then(|output| {
+                    if let Some(target) = target {
+                        self.set_metadata_origin(&target, name)?;
+                    }
+                    Ok(output)
+                });
+            let output = res.map_err(|e| ProcedureError::Chain {
+                index,
+                error: Box::new(e),
+            })?;
+            out.push(output);
+        }
+        Ok(out)
+    }
+
+    /// Creates a copy of the vaults that the procedures access, on which they can be executed outside of the actor.
+    /// Vaults that do not exist yet are created in the copy.  The ephemeral vault always starts empty.
+    pub(crate) fn staging(&mut self, procedures: &[StrongholdProcedure]) -> Staging {
+        let mut staging = SecureClient::new(self.client_id);
+        staging.remote_peer = self.remote_peer.clone();
+        staging.custom_procedures = self.custom_procedures.clone();
+
+        let ephemeral = Self::derive_vault_id(EPHEMERAL_VAULT_PATH.to_vec());
+        let vault_ids: HashSet<VaultId> = procedures
+            .iter()
+            .flat_map(StrongholdProcedure::vault_ids)
+            .filter(|vault_id| *vault_id != ephemeral)
+            .collect();
+        let revisions = vault_ids
+            .iter()
+            .map(|vault_id| (*vault_id, self.revisions.get(*vault_id)))
+            .collect();
+        for vault_id in vault_ids {
+            if let Some(key) = self.keystore.take_key(vault_id) {
+                staging.keystore.insert_key(vault_id, key.clone());
+                self.keystore.insert_key(vault_id, key);
+            }
+            if let Some(vault) = self.db.vaults.get(&vault_id) {
+                staging.db.vaults.insert(vault_id, vault.clone());
+            }
+            let state = &mut staging.client_state;
+            if let Some(policy) = self.client_state.policies.get(&vault_id) {
+                state.policies.insert(vault_id, policy.clone());
+            }
+            if let Some(expirations) = self.client_state.expirations.get(&vault_id) {
+                state.expirations.insert(vault_id, expirations.clone());
+            }
+            if let Some(written_once) = self.client_state.written_once.get(&vault_id) {
+                state.written_once.insert(vault_id, written_once.clone());
+            }
+        }
+        Staging {
+            client: staging,
+            revisions,
+        }
+    }
+
+    /// Replaces the vaults of the client with the ones of a `staging` copy that were changed by the procedures, and
+    /// records the last use of the records that they used in the other vaults.  The ephemeral vault is not committed.
+    ///
+    /// Fails with [`ProcedureError::Conflict`] if one of the changed vaults was also changed in the client since the
+    /// copy was created, and with a [`FatalProcedureError`] if the procedures created a vault that was not copied.
+    pub(crate) fn commit(&mut self, staging: Staging) -> Result<(), ProcedureError> {
+        let Staging {
+            client: mut staging,
+            revisions,
+        } = staging;
+        let ephemeral = Self::derive_vault_id(EPHEMERAL_VAULT_PATH.to_vec());
+        staging.db.vaults.remove(&ephemeral);
+
+        if let Some(vault_id) = staging
+            .db
+            .vaults
+            .keys()
+            .find(|vault_id| !revisions.contains_key(vault_id))
+        {
+            let e = format!("procedures created vault {:?} that was not staged", vault_id);
+            return Err(FatalProcedureError::from(e).into());
+        }
+        let changed: HashSet<VaultId> = revisions
+            .keys()
+            .filter(|vault_id| staging.revisions.changed(**vault_id))
+            .copied()
+            .collect();
+        if changed
+            .iter()
+            .any(|vault_id| self.revisions.get(*vault_id) != revisions[vault_id])
+        {
+            return Err(ProcedureError::Conflict);
+        }
+
+        for vault_id in changed.iter().copied() {
+            if let Some(vault) = staging.db.vaults.remove(&vault_id) {
+                self.db.vaults.insert(vault_id, vault);
+            }
+            if let Some(key) = staging.keystore.take_key(vault_id) {
+                self.keystore.take_key(vault_id);
+                self.keystore.insert_key(vault_id, key);
+            }
+            let state = &mut staging.client_state;
+            match state.expirations.remove(&vault_id) {
+                Some(expirations) => self.client_state.expirations.insert(vault_id, expirations),
+                None => self.client_state.expirations.remove(&vault_id),
+            };
+            match state.written_once.remove(&vault_id) {
+                Some(written_once) => self.client_state.written_once.insert(vault_id, written_once),
+                None => self.client_state.written_once.remove(&vault_id),
+            };
+            self.revisions.bump(vault_id);
+        }
+        for (vault_id, record_id) in staging.revisions.used {
+            if changed.contains(&vault_id) {
+                continue;
+            }
+            if let Some(mut metadata) = self.read_metadata(vault_id, record_id) {
+                metadata.last_used = Some(unix_time());
+                // The procedures already succeeded, a failure to record the use does not revert them.
+                let _ = self.write_metadata(vault_id, record_id, &metadata);
+            }
+        }
+        Ok(())
+    }
+
+    /// Revokes all records at ephemeral locations and removes them from the vault.
+    fn clear_ephemeral(&mut self) {
+        let vault_id = Self::derive_vault_id(EPHEMERAL_VAULT_PATH.to_vec());
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return,
+        };
+        for (record_id, _) in self.db.list_hints_and_ids(&key, vault_id) {
+            let _ = self.db.revoke_record(&key, vault_id, record_id);
+            self.set_lifetime(vault_id, record_id, None);
+        }
+        self.db.garbage_collect_vault(&key, vault_id);
+        self.keystore.insert_key(vault_id, key);
+    }
+
     /// Resolves a location to a `VaultId` and a `RecordId`
     pub fn resolve_location<L: AsRef<Location>>(l: L) -> (VaultId, RecordId) {
         match l.as_ref() {
@@ -136,30 +625,70 @@ impl SecureClient {
 
         ctr
     }
+
+    /// Rotates the key of the vault with the given [`VaultId`]. All live records are re-sealed under a freshly
+    /// generated key, revoked records are garbage collected. If re-sealing fails, the vault and its key are left
+    /// unchanged. Returns `false` if the vault does not exist.
+    pub fn rotate_vault_key(&mut self, vault_id: VaultId) -> Result<bool, RecordError> {
+        let key = match self.keystore.take_key(vault_id) {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        let new_key = Key::random();
+
+        match self.db.rotate_key(&key, &new_key, vault_id) {
+            Ok(()) => {
+                self.keystore.insert_key(vault_id, new_key);
+                self.revisions.bump(vault_id);
+                Ok(true)
+            }
+            Err(e) => {
+                self.keystore.insert_key(vault_id, key);
+                match e {
+                    EngineVaultError::VaultNotFound(_) => Ok(false),
+                    EngineVaultError::Record(e) => Err(e),
+                    EngineVaultError::Procedure(e) => match e {},
+                }
+            }
+        }
+    }
+}
+
+/// Current time in seconds since the unix epoch.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
 }
 
 impl Runner for SecureClient {
-    fn get_guard<F, T>(&mut self, location: &Location, f: F) -> Result<T, VaultError<FatalProcedureError>>
+    fn get_guard<F, T>(&mut self, location: &Location, kind: ProcedureKind, f: F) -> Result<T, ProcedureError>
     where
         F: FnOnce(GuardedVec<u8>) -> Result<T, FatalProcedureError>,
     {
         let (vault_id, record_id) = Self::resolve_location(location);
+        self.check_policy_kind(vault_id, kind)?;
+        self.revoke_expired(vault_id);
         let key = self
             .keystore
             .take_key(vault_id)
-            .ok_or(VaultError::VaultNotFound(vault_id))?;
+            .ok_or(VaultError::<FatalProcedureError>::VaultNotFound(vault_id))?;
 
         let mut ret = None;
         let execute_procedure = |guard: GuardedVec<u8>| {
             ret = Some(f(guard)?);
-            Ok(())
+            Ok::<_, FatalProcedureError>(())
         };
         let res = self.db.get_guard(&key, vault_id, record_id, execute_procedure);
         self.keystore.insert_key(vault_id, key);
 
         match res {
-            Ok(()) => Ok(ret.unwrap()),
-            Err(e) => Err(e),
+            Ok(()) => {
+                self.touch_metadata(vault_id, record_id)?;
+                Ok(ret.unwrap())
+            }
+            Err(e) => Err(e.into()),
         }
     }
 
@@ -168,21 +697,30 @@ impl Runner for SecureClient {
         location0: &Location,
         location1: &Location,
         hint: RecordHint,
+        lifetime: Option<Duration>,
+        kind: ProcedureKind,
         f: F,
-    ) -> Result<T, VaultError<FatalProcedureError>>
+    ) -> Result<T, ProcedureError>
     where
         F: FnOnce(GuardedVec<u8>) -> Result<Products<T>, FatalProcedureError>,
     {
         let (vid0, rid0) = Self::resolve_location(location0);
         let (vid1, rid1) = Self::resolve_location(location1);
 
-        let key0 = self.keystore.take_key(vid0).ok_or(VaultError::VaultNotFound(vid0))?;
+        self.check_policy_kind(vid0, kind)?;
+        self.check_policy_write(vid1, rid1)?;
+        self.revoke_expired(vid0);
+
+        let key0 = self
+            .keystore
+            .take_key(vid0)
+            .ok_or(VaultError::<FatalProcedureError>::VaultNotFound(vid0))?;
 
         let mut ret = None;
         let execute_procedure = |guard: GuardedVec<u8>| {
             let Products { output: plain, secret } = f(guard)?;
             ret = Some(plain);
-            Ok(secret)
+            Ok::<_, FatalProcedureError>(secret)
         };
 
         let res;
@@ -205,13 +743,33 @@ impl Runner for SecureClient {
         self.keystore.insert_key(vid0, key0);
 
         match res {
-            Ok(()) => Ok(ret.unwrap()),
-            Err(e) => Err(e),
+            Ok(()) => {
+                self.revisions.bump(vid1);
+                self.mark_written(vid1, rid1);
+                self.set_lifetime(vid1, rid1, lifetime);
+                self.touch_metadata(vid0, rid0)?;
+                let metadata = RecordMetadata {
+                    created: unix_time(),
+                    last_used: None,
+                    origin: None,
+                    source: Some(location0.clone()),
+                };
+                self.write_metadata(vid1, rid1, &metadata)?;
+                Ok(ret.unwrap())
+            }
+            Err(e) => Err(e.into()),
         }
     }
 
-    fn write_to_vault(&mut self, location: &Location, hint: RecordHint, value: Vec<u8>) -> Result<(), RecordError> {
+    fn write_to_vault(
+        &mut self,
+        location: &Location,
+        hint: RecordHint,
+        lifetime: Option<Duration>,
+        value: Vec<u8>,
+    ) -> Result<(), ProcedureError> {
         let (vault_id, record_id) = Self::resolve_location(location);
+        self.check_policy_write(vault_id, record_id)?;
         if !self.keystore.vault_exists(vault_id) {
             let key = self.keystore.create_key(vault_id);
             self.db.init_vault(key, vault_id);
@@ -219,7 +777,18 @@ impl Runner for SecureClient {
         let key = self.keystore.take_key(vault_id).unwrap();
         let res = self.db.write(&key, vault_id, record_id, &value, hint);
         self.keystore.insert_key(vault_id, key);
-        res
+        res?;
+        self.revisions.bump(vault_id);
+        self.mark_written(vault_id, record_id);
+        self.set_lifetime(vault_id, record_id, lifetime);
+        let metadata = RecordMetadata {
+            created: unix_time(),
+            last_used: None,
+            origin: None,
+            source: None,
+        };
+        self.write_metadata(vault_id, record_id, &metadata)?;
+        Ok(())
     }
 
     fn revoke_data(&mut self, location: &Location) -> Result<(), RecordError> {
@@ -228,19 +797,36 @@ impl Runner for SecureClient {
             let res = self.db.revoke_record(&key, vault_id, record_id);
             self.keystore.insert_key(vault_id, key);
             res?;
+            self.revisions.bump(vault_id);
+            self.set_lifetime(vault_id, record_id, None);
         }
         Ok(())
     }
 
     fn garbage_collect(&mut self, vault_id: VaultId) -> bool {
+        self.revoke_expired(vault_id);
         let key = match self.keystore.take_key(vault_id) {
             Some(key) => key,
             None => return false,
         };
         self.db.garbage_collect_vault(&key, vault_id);
         self.keystore.insert_key(vault_id, key);
+        self.revisions.bump(vault_id);
         true
     }
+
+    fn execute_custom(&mut self, procedure: CustomProcedure) -> Result<ProcedureOutput, ProcedureError> {
+        let executor = self
+            .custom_procedures
+            .read()
+            .map_err(|e| FatalProcedureError::from(e.to_string()))?
+            .get(&procedure.name)
+            .copied();
+        match executor {
+            Some(execute) => execute(procedure, self),
+            None => Err(FatalProcedureError::from(format!("unknown procedure {}", procedure.name)).into()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -3,11 +3,17 @@
 
 #![allow(clippy::type_complexity)]
 
-use crate::{state::secure::Store, Provider};
+use crate::{
+    state::secure::{ClientState, Store},
+    Provider,
+};
 
 use engine::{
-    snapshot::{self, read_from, write_to, Key, ReadError as EngineReadError, WriteError as EngineWriteError},
-    vault::{ClientId, DbView, Key as PKey, VaultId},
+    snapshot::{
+        self, read_header_from, read_snapshot, write_snapshot, ReadError as EngineReadError, SnapshotHeader,
+        SnapshotInfo, SnapshotKey, WriteError as EngineWriteError, OLD_VERSION,
+    },
+    vault::{ClientId, DbView, Key as PKey, LegacyDbView, VaultId},
 };
 
 use serde::{Deserialize, Serialize};
@@ -18,23 +24,39 @@ use thiserror::Error as DeriveError;
 #[derive(Default)]
 pub struct Snapshot {
     pub state: SnapshotState,
+
+    /// Label that is recorded in the header of written snapshots.
+    pub label: Option<String>,
 }
 
 /// Data structure that is written to the snapshot.
 #[derive(Deserialize, Serialize, Default)]
-pub struct SnapshotState(HashMap<ClientId, (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store)>);
+pub struct SnapshotState(HashMap<ClientId, (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store, ClientState)>);
+
+/// Data structure that was written to snapshots of version 2.0, before the [`ClientState`] was persisted and records
+/// had metadata.
+#[derive(Deserialize)]
+struct LegacySnapshotState(HashMap<ClientId, (HashMap<VaultId, PKey<Provider>>, LegacyDbView<Provider>, Store)>);
 
 impl Snapshot {
     /// Creates a new [`Snapshot`] from a buffer of [`SnapshotState`] state.
     pub fn new(state: SnapshotState) -> Self {
-        Self { state }
+        Self { state, label: None }
     }
 
     /// Gets the state component parts as a tuple.
-    pub fn get_state(&mut self, id: ClientId) -> (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store) {
+    pub fn get_state(
+        &mut self,
+        id: ClientId,
+    ) -> (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store, ClientState) {
         match self.state.0.remove(&id) {
             Some(t) => t,
-            None => (HashMap::default(), DbView::default(), Store::default()),
+            None => (
+                HashMap::default(),
+                DbView::default(),
+                Store::default(),
+                ClientState::default(),
+            ),
         }
     }
 
@@ -45,30 +67,72 @@ impl Snapshot {
 
     /// Reads state from the specified named snapshot or the specified path
     /// TODO: Add associated data.
-    pub fn read_from_snapshot(name: Option<&str>, path: Option<&Path>, key: Key) -> Result<Self, ReadError> {
-        let state = match path {
-            Some(p) => read_from(p, &key, &[])?,
-            None => read_from(&snapshot::files::get_path(name)?, &key, &[])?,
+    pub fn read_from_snapshot(name: Option<&str>, path: Option<&Path>, key: SnapshotKey) -> Result<Self, ReadError> {
+        let (header, state) = match path {
+            Some(p) => read_snapshot(p, &key, &[])?,
+            None => read_snapshot(&snapshot::files::get_path(name)?, &key, &[])?,
         };
 
-        let data =
-            SnapshotState::deserialize(state).map_err(|_| ReadError::CorruptedContent("Decryption failed.".into()))?;
+        let data = SnapshotState::deserialize_version(state, header.version)
+            .map_err(|_| ReadError::CorruptedContent("Decryption failed.".into()))?;
 
-        Ok(Self::new(data))
+        Ok(Self {
+            state: data,
+            label: header.info.label,
+        })
+    }
+
+    /// Reads the header of the specified named snapshot or the specified path, without decrypting it.
+    pub fn read_header(name: Option<&str>, path: Option<&Path>) -> Result<SnapshotHeader, ReadError> {
+        let header = match path {
+            Some(p) => read_header_from(p)?,
+            None => read_header_from(&snapshot::files::get_path(name)?)?,
+        };
+        Ok(header)
+    }
+
+    /// Re-encrypts the snapshot at the specified path under `new_key`. The state of all clients is loaded with
+    /// `old_key` and atomically written back, keeping the label of the snapshot.
+    pub fn rekey(path: &Path, old_key: &SnapshotKey, new_key: &SnapshotKey) -> Result<(), RekeyError> {
+        let (header, data) = read_snapshot(path, old_key, &[]).map_err(ReadError::from)?;
+
+        let state = SnapshotState::deserialize_version(data, header.version)
+            .map_err(|_| ReadError::CorruptedContent("Decryption failed.".into()))?;
+        let data = state
+            .serialize()
+            .map_err(|_| WriteError::CorruptedData("Serialization failed.".into()))?;
+
+        let info = SnapshotInfo {
+            client_ids: state.0.keys().copied().collect(),
+            label: header.info.label,
+        };
+        write_snapshot(&data, path, new_key, info, &[]).map_err(WriteError::from)?;
+
+        Ok(())
     }
 
     /// Writes state to the specified named snapshot or the specified path
     /// TODO: Add associated data.
-    pub fn write_to_snapshot(&self, name: Option<&str>, path: Option<&Path>, key: Key) -> Result<(), WriteError> {
+    pub fn write_to_snapshot(
+        &self,
+        name: Option<&str>,
+        path: Option<&Path>,
+        key: SnapshotKey,
+    ) -> Result<(), WriteError> {
         let data = self
             .state
             .serialize()
             .map_err(|_| WriteError::CorruptedData("Serialization failed.".into()))?;
 
+        let info = SnapshotInfo {
+            client_ids: self.state.0.keys().copied().collect(),
+            label: self.label.clone(),
+        };
+
         // TODO: This is a hack and probably should be removed when we add proper error handling.
         let f = move || match path {
-            Some(p) => write_to(&data, p, &key, &[]),
-            None => write_to(&data, &snapshot::files::get_path(name)?, &key, &[]),
+            Some(p) => write_snapshot(&data, p, &key, info.clone(), &[]),
+            None => write_snapshot(&data, &snapshot::files::get_path(name)?, &key, info.clone(), &[]),
         };
 
         match f() {
@@ -80,7 +144,7 @@ impl Snapshot {
 
 impl SnapshotState {
     /// Creates a new snapshot state.
-    pub fn new(id: ClientId, data: (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store)) -> Self {
+    pub fn new(id: ClientId, data: (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store, ClientState)) -> Self {
         let mut state = HashMap::new();
         state.insert(id, data);
 
@@ -88,7 +152,11 @@ impl SnapshotState {
     }
 
     /// Adds data to the snapshot state hashmap.
-    pub fn add_data(&mut self, id: ClientId, data: (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store)) {
+    pub fn add_data(
+        &mut self,
+        id: ClientId,
+        data: (HashMap<VaultId, PKey<Provider>>, DbView<Provider>, Store, ClientState),
+    ) {
         self.0.insert(id, data);
     }
 
@@ -101,6 +169,20 @@ impl SnapshotState {
     pub fn deserialize(data: Vec<u8>) -> bincode::Result<Self> {
         bincode::deserialize(&data)
     }
+
+    /// Deserializes the snapshot state from bytes that were written with the given snapshot format version.
+    /// Version 2.0 snapshots did not contain the [`ClientState`] of the clients, it is loaded empty.
+    pub fn deserialize_version(data: Vec<u8>, version: [u8; 2]) -> bincode::Result<Self> {
+        if version != OLD_VERSION {
+            return Self::deserialize(data);
+        }
+        let LegacySnapshotState(legacy) = bincode::deserialize(&data)?;
+        let state = legacy
+            .into_iter()
+            .map(|(id, (keys, db, store))| (id, (keys, db.into(), store, ClientState::default())))
+            .collect();
+        Ok(Self(state))
+    }
 }
 
 #[derive(Debug, DeriveError)]
@@ -125,6 +207,7 @@ impl From<EngineReadError> for ReadError {
                 "Unsupported version: expected {:?}, found {:?}.",
                 expected, found
             )),
+            EngineReadError::NoPasswordKdf => ReadError::InvalidFile("Snapshot is not protected by a password.".into()),
         }
     }
 }
@@ -147,3 +230,12 @@ impl From<EngineWriteError> for WriteError {
         }
     }
 }
+
+#[derive(Debug, DeriveError)]
+pub enum RekeyError {
+    #[error("reading snapshot failed: {0}")]
+    Read(#[from] ReadError),
+
+    #[error("writing snapshot failed: {0}")]
+    Write(#[from] WriteError),
+}
@@ -38,3 +38,10 @@ pub fn hd_path() -> (String, Chain) {
     }
     (s, Chain::from_u32_hardened(is))
 }
+
+/// Creates a random salt of 16 bytes.
+pub fn salt() -> Vec<u8> {
+    let mut bs = vec![0; 16];
+    fill(&mut bs).expect("Unable to fill salt");
+    bs
+}
@@ -1,7 +1,11 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{ActorError, Location, RecordHint, Stronghold};
+use crate::{
+    utils::LoadFromPath, ActorError, Argon2Params, AuditError, AuditLog, AuditOperation, AuditOutcome, AuditQuery,
+    KeyDerivation, Location, RecordHint, SnapshotKey, Stronghold,
+};
+use engine::vault::ClientId;
 use stronghold_utils::random::bytestring;
 
 #[actix::test]
@@ -387,3 +391,368 @@ async fn test_stronghold_generics() {
         .unwrap_or_else(|e| panic!("Actor error: {}", e))
         .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
 }
+
+#[actix::test]
+async fn test_password_snapshot() {
+    let client_path = b"password_client".to_vec();
+    let loc = Location::generic("path", "record");
+    let password = b"correct horse battery staple".to_vec();
+    let params = Argon2Params {
+        memory_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    let mut stronghold = Stronghold::init_stronghold_system(client_path.clone(), vec![])
+        .await
+        .unwrap();
+
+    stronghold
+        .write_to_vault(
+            loc.clone(),
+            b"password protected".to_vec(),
+            RecordHint::new(b"hint").unwrap(),
+            vec![],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    stronghold
+        .write_all_to_snapshot_with_password(&password, Some(params), Some("password_snapshot".into()), None)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
+
+    stronghold.kill_stronghold(client_path.clone(), false).await.unwrap();
+    assert!(stronghold
+        .read_secret(client_path.clone(), loc.clone())
+        .await
+        .unwrap()
+        .is_none());
+
+    // a wrong password does not open the snapshot.
+    let wrong_password = b"wrong password".to_vec();
+    assert!(stronghold
+        .read_snapshot_with_password(
+            client_path.clone(),
+            None,
+            &wrong_password,
+            Some("password_snapshot".into()),
+            None
+        )
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .read_snapshot_with_password(
+            client_path.clone(),
+            None,
+            &password,
+            Some("password_snapshot".into()),
+            None,
+        )
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Read snapshot error: {}", e));
+
+    let p = stronghold.read_secret(client_path, loc).await.unwrap();
+    assert_eq!(std::str::from_utf8(&p.unwrap()), Ok("password protected"));
+}
+
+#[actix::test]
+async fn test_snapshot_header() {
+    let client_path = b"header_client".to_vec();
+    let key_data = b"abcdefghijklmnopqrstuvwxyz012345".to_vec();
+
+    let mut stronghold = Stronghold::init_stronghold_system(client_path.clone(), vec![])
+        .await
+        .unwrap();
+
+    stronghold.set_snapshot_label(Some("backup".into())).await.unwrap();
+    stronghold
+        .write_all_to_snapshot(&key_data, Some("header_snapshot".into()), None)
+        .await
+        .unwrap_or_else(|e| panic!("Actor error: {}", e))
+        .unwrap_or_else(|e| panic!("Write snapshot error: {}", e));
+
+    let header = Stronghold::read_snapshot_header(Some("header_snapshot".into()), None).unwrap();
+    assert_eq!(header.version, engine::snapshot::VERSION);
+    assert_eq!(header.kdf, KeyDerivation::None);
+    assert!(header.created_at.is_some());
+    assert_eq!(header.info.label.as_deref(), Some("backup"));
+    assert_eq!(
+        header.info.client_ids,
+        vec![ClientId::load_from_path(&client_path, &client_path)]
+    );
+}
+
+#[actix::test]
+async fn test_rotate_vault_key() {
+    let client_path = b"rotation_client".to_vec();
+    let vault_path = b"rotation_vault".to_vec();
+    let loc0 = Location::generic(vault_path.clone(), b"record0".to_vec());
+    let loc1 = Location::generic(vault_path.clone(), b"record1".to_vec());
+    let key_data = b"abcdefghijklmnopqrstuvwxyz012345".to_vec();
+
+    let mut stronghold = Stronghold::init_stronghold_system(client_path.clone(), vec![])
+        .await
+        .unwrap();
+
+    for (loc, payload) in [(&loc0, b"secret0"), (&loc1, b"secret1")] {
+        stronghold
+            .write_to_vault(loc.clone(), payload.to_vec(), RecordHint::new(b"hint").unwrap(), vec![])
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    let mut ids = stronghold.list_hints_and_ids(vault_path.clone()).await.unwrap();
+
+    assert!(stronghold.rotate_vault_key(vault_path.clone()).await.unwrap().unwrap());
+    assert!(!stronghold
+        .rotate_vault_key(b"no_vault".to_vec())
+        .await
+        .unwrap()
+        .unwrap());
+
+    let mut ids_rotated = stronghold.list_hints_and_ids(vault_path.clone()).await.unwrap();
+    ids.sort_by_key(|(id, _)| id.to_string());
+    ids_rotated.sort_by_key(|(id, _)| id.to_string());
+    assert_eq!(ids, ids_rotated);
+
+    let p = stronghold.read_secret(client_path.clone(), loc0.clone()).await.unwrap();
+    assert_eq!(p.as_deref(), Some(&b"secret0"[..]));
+
+    // the rotated key is persisted in the snapshot.
+    stronghold
+        .write_all_to_snapshot(&key_data, Some("rotation".into()), None)
+        .await
+        .unwrap()
+        .unwrap();
+    stronghold.kill_stronghold(client_path.clone(), false).await.unwrap();
+    stronghold
+        .read_snapshot(client_path.clone(), None, &key_data, Some("rotation".into()), None)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let p = stronghold.read_secret(client_path, loc1).await.unwrap();
+    assert_eq!(p.as_deref(), Some(&b"secret1"[..]));
+}
+
+#[actix::test]
+async fn test_rekey_snapshot() {
+    let client_path0 = b"rekey_client0".to_vec();
+    let client_path1 = b"rekey_client1".to_vec();
+    let loc = Location::generic("path", "record");
+    let old_key = b"abcdefghijklmnopqrstuvwxyz012345".to_vec();
+    let mut raw_key = [0u8; 32];
+    raw_key.copy_from_slice(&old_key);
+    let new_password = b"new password".to_vec();
+    let params = Argon2Params {
+        memory_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    let mut stronghold = Stronghold::init_stronghold_system(client_path0.clone(), vec![])
+        .await
+        .unwrap();
+    stronghold
+        .spawn_stronghold_actor(client_path1.clone(), vec![])
+        .await
+        .unwrap();
+
+    for client_path in [&client_path0, &client_path1] {
+        stronghold.switch_actor_target(client_path.clone()).await.unwrap();
+        stronghold
+            .write_to_vault(
+                loc.clone(),
+                client_path.clone(),
+                RecordHint::new(b"hint").unwrap(),
+                vec![],
+            )
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    stronghold.set_snapshot_label(Some("rekeyed".into())).await.unwrap();
+    stronghold
+        .write_all_to_snapshot(&old_key, Some("rekey".into()), None)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let path = engine::snapshot::files::get_path(Some("rekey")).unwrap();
+    let new_key = SnapshotKey::Password(new_password.clone().into(), params);
+
+    // a wrong key leaves the snapshot untouched.
+    assert!(Stronghold::rekey_snapshot(&path, &SnapshotKey::Key([0u8; 32]), &new_key).is_err());
+
+    Stronghold::rekey_snapshot(&path, &SnapshotKey::Key(raw_key), &new_key).unwrap();
+
+    let header = Stronghold::read_snapshot_header(Some("rekey".into()), None).unwrap();
+    assert!(matches!(header.kdf, KeyDerivation::Argon2id { .. }));
+    assert_eq!(header.info.client_ids.len(), 2);
+    assert_eq!(header.info.label.as_deref(), Some("rekeyed"));
+
+    // the old key no longer opens the snapshot.
+    let mut stronghold = Stronghold::init_stronghold_system(client_path0.clone(), vec![])
+        .await
+        .unwrap();
+    assert!(stronghold
+        .read_snapshot(client_path0.clone(), None, &old_key, Some("rekey".into()), None)
+        .await
+        .unwrap()
+        .is_err());
+
+    stronghold
+        .spawn_stronghold_actor(client_path1.clone(), vec![])
+        .await
+        .unwrap();
+    for client_path in [&client_path0, &client_path1] {
+        stronghold.switch_actor_target(client_path.clone()).await.unwrap();
+        stronghold
+            .read_snapshot_with_password(client_path.clone(), None, &new_password, Some("rekey".into()), None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let p = stronghold.read_secret(client_path.clone(), loc.clone()).await.unwrap();
+        assert_eq!(p.as_ref(), Some(client_path));
+    }
+}
+
+#[actix::test]
+async fn test_audit_log() {
+    let client_path = b"audit_client".to_vec();
+    let vault_path = b"audit_vault".to_vec();
+    let loc = Location::generic(vault_path.clone(), b"record".to_vec());
+    let key_data = b"abcdefghijklmnopqrstuvwxyz012345".to_vec();
+
+    let mut stronghold = Stronghold::init_stronghold_system(client_path.clone(), vec![])
+        .await
+        .unwrap();
+
+    stronghold
+        .write_to_vault(
+            loc.clone(),
+            b"secret".to_vec(),
+            RecordHint::new(b"hint").unwrap(),
+            vec![],
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    stronghold
+        .write_to_store(b"key".to_vec(), b"value".to_vec(), None)
+        .await
+        .unwrap();
+    assert!(stronghold.read_from_store(b"missing".to_vec()).await.unwrap().is_none());
+    assert!(stronghold.rotate_vault_key(vault_path.clone()).await.unwrap().unwrap());
+    stronghold.delete_data(loc.clone(), true).await.unwrap().unwrap();
+
+    let entries = stronghold.read_audit_log(AuditQuery::default()).await.unwrap();
+    assert!(entries.iter().all(|e| e.peer.is_none()));
+    assert_eq!(
+        entries.first().map(|e| &e.operation),
+        Some(&AuditOperation::Write(loc.clone()))
+    );
+    assert!(entries
+        .iter()
+        .any(|e| e.operation == AuditOperation::StoreWrite(b"key".to_vec())));
+    assert!(entries
+        .iter()
+        .any(|e| e.operation == AuditOperation::RotateVaultKey(vault_path.clone())));
+    assert!(entries
+        .iter()
+        .any(|e| e.operation == AuditOperation::Revoke(loc.clone())));
+    assert!(entries
+        .iter()
+        .any(|e| e.operation == AuditOperation::GarbageCollect(vault_path.clone())));
+
+    let query = AuditQuery {
+        failures_only: true,
+        ..Default::default()
+    };
+    let failures = stronghold.read_audit_log(query).await.unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].operation, AuditOperation::StoreRead(b"missing".to_vec()));
+    assert!(matches!(failures[0].outcome, AuditOutcome::Failure(_)));
+
+    let query = AuditQuery {
+        vault_path: Some(vault_path.clone()),
+        ..Default::default()
+    };
+    let vault_entries = stronghold.read_audit_log(query).await.unwrap();
+    assert!(vault_entries.iter().all(|e| e.operation.touches_vault(&vault_path)));
+    assert_eq!(vault_entries.len(), 4);
+
+    let head = stronghold.verify_audit_log().await.unwrap().unwrap();
+    assert_eq!(head, entries.last().unwrap().hash);
+
+    // the log and its key are persisted in the snapshot, and the operations of a client before it loads the snapshot
+    // are appended to its log.
+    stronghold
+        .write_all_to_snapshot(&key_data, Some("audit".into()), None)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut restored = Stronghold::init_stronghold_system(client_path.clone(), vec![])
+        .await
+        .unwrap();
+    restored
+        .write_to_store(b"other".to_vec(), b"value".to_vec(), None)
+        .await
+        .unwrap();
+    restored
+        .read_snapshot(client_path.clone(), None, &key_data, Some("audit".into()), None)
+        .await
+        .unwrap()
+        .unwrap();
+    let reloaded = restored.read_audit_log(AuditQuery::default()).await.unwrap();
+    assert_eq!(reloaded[..entries.len()], entries[..]);
+    let operations: Vec<_> = reloaded[entries.len()..].iter().map(|e| e.operation.clone()).collect();
+    assert_eq!(
+        operations,
+        vec![
+            AuditOperation::StoreWrite(b"other".to_vec()),
+            AuditOperation::LoadSnapshot
+        ]
+    );
+    assert!(restored.verify_audit_log().await.unwrap().is_ok());
+
+    // the log of the client is kept when its data is cleared or replaced.
+    stronghold.kill_stronghold(client_path.clone(), false).await.unwrap();
+    stronghold
+        .read_snapshot(client_path, None, &key_data, Some("audit".into()), None)
+        .await
+        .unwrap()
+        .unwrap();
+    let kept = stronghold.read_audit_log(AuditQuery::default()).await.unwrap();
+    assert_eq!(kept[..entries.len()], entries[..]);
+    let operations: Vec<_> = kept[entries.len()..].iter().map(|e| e.operation.clone()).collect();
+    assert_eq!(
+        operations,
+        vec![AuditOperation::ClearCache, AuditOperation::LoadSnapshot]
+    );
+    assert_eq!(
+        stronghold.verify_audit_log().await.unwrap(),
+        Ok(kept.last().unwrap().hash)
+    );
+
+    // modifications of past entries are detected, and entries can not be hashed without the key.
+    let key = bytestring(32);
+    let mut log = AuditLog::default();
+    for entry in entries {
+        log.append(&key, entry.operation, entry.outcome, entry.peer);
+    }
+    assert!(log.verify(&key).is_ok());
+    assert_eq!(log.verify(&bytestring(32)), Err(AuditError::InvalidHash(0)));
+    log.entries[1].outcome = AuditOutcome::Failure("tampered".into());
+    assert_eq!(log.verify(&key), Err(AuditError::InvalidHash(1)));
+    log.entries.remove(1);
+    assert_eq!(log.verify(&key), Err(AuditError::InvalidIndex(1)));
+}
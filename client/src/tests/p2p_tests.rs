@@ -6,7 +6,7 @@ use crate::{
     procedures::{Slip10Derive, Slip10DeriveInput, Slip10Generate},
     state::p2p::{ClientAccess, FirewallChannel, FirewallChannelSender},
     tests::fresh,
-    Location, Stronghold,
+    AuditOperation, AuditQuery, Location, Stronghold,
 };
 use futures::StreamExt;
 use stronghold_utils::random::bytestring;
@@ -202,6 +202,7 @@ async fn test_stronghold_p2p() {
                     chain,
                     hint: fresh::record_hint(),
                     input: Slip10DeriveInput::Seed(seed1),
+                    lifetime: None,
                 },
             )
             .await
@@ -238,6 +239,7 @@ async fn test_stronghold_p2p() {
                 size_bytes: None,
                 output: seed1_clone,
                 hint: fresh::record_hint(),
+                lifetime: None,
             })
             .await
             .unwrap_or_else(|e| panic!("Could not execute remote procedure: {}", e))
@@ -325,6 +327,41 @@ async fn test_p2p_config() {
     }
 }
 
+#[actix::test]
+async fn test_p2p_audit_log() {
+    let Setup {
+        local_stronghold,
+        local_id,
+        remote_stronghold,
+        remote_id,
+        remote_client,
+    } = spawn_peers(FirewallSetup::default(), None).await;
+
+    let key = bytestring(10);
+    local_stronghold
+        .write_to_remote_store(remote_id, remote_client.clone(), key.clone(), bytestring(10), None)
+        .await
+        .unwrap();
+    remote_stronghold
+        .write_to_store(bytestring(10), bytestring(10), None)
+        .await
+        .unwrap();
+
+    let entries = remote_stronghold.read_audit_log(AuditQuery::default()).await.unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].operation, AuditOperation::StoreWrite(key));
+    assert_eq!(entries[0].peer, Some(local_id.to_base58()));
+    assert_eq!(entries[1].peer, None);
+
+    let query = AuditQuery {
+        peer: Some(local_id.to_base58()),
+        ..Default::default()
+    };
+    let remote_entries = remote_stronghold.read_audit_log(query).await.unwrap();
+    assert_eq!(remote_entries, entries[..1]);
+    assert!(remote_stronghold.verify_audit_log().await.unwrap().is_ok());
+}
+
 #[actix::test]
 async fn test_p2p_firewall() {
     let system = actix::System::current();
@@ -418,6 +455,7 @@ async fn test_p2p_firewall() {
             size_bytes: None,
             output: loc3.clone(),
             hint: fresh::record_hint(),
+            lifetime: None,
         };
 
         let loc4 = Location::generic(allowed_vault_path.clone(), fresh::bytestring(1024));
@@ -426,6 +464,7 @@ async fn test_p2p_firewall() {
             chain: fresh::hd_path().1,
             output: loc4,
             hint: fresh::record_hint(),
+            lifetime: None,
         };
 
         let res = local_stronghold
@@ -446,6 +485,7 @@ async fn test_p2p_firewall() {
             size_bytes: None,
             output: loc5,
             hint: fresh::record_hint(),
+            lifetime: None,
         };
         let res = local_stronghold
             .remote_runtime_exec(remote_id, allowed_client_path.clone(), proc_generate)
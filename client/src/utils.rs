@@ -6,7 +6,10 @@ mod types;
 
 pub use self::{
     ids::LoadFromPath,
-    types::{Location, StrongholdFlags, VaultFlags},
+    types::{
+        Location, PolicyError, RecordInfo, RecordMetadata, StrongholdFlags, VaultFlags, VaultPolicy, AUDIT_VAULT_PATH,
+        EPHEMERAL_VAULT_PATH,
+    },
 };
 
 /// Gets the index of a slice.
@@ -1,7 +1,16 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::procedures::ProcedureKind;
+use engine::vault::{RecordHint, RecordId};
 use serde::{Deserialize, Serialize};
+use thiserror::Error as DeriveError;
+
+/// Vault path of the [`Location::ephemeral`] locations.
+pub const EPHEMERAL_VAULT_PATH: &[u8] = b"stronghold::ephemeral";
+
+/// Vault path under which a client holds the key of its [`AuditLog`][crate::AuditLog].
+pub const AUDIT_VAULT_PATH: &[u8] = b"stronghold::audit";
 
 /// A `Location` type used to specify where in the `Stronghold` a piece of data should be stored. A generic location
 /// specifies a non-versioned location while a counter location specifies a versioned location. The Counter location can
@@ -14,7 +23,7 @@ use serde::{Deserialize, Serialize};
 /// result in the system panicking if the upper bound is reached!
 /// For users that write a large number of secrets into Stronghold, we strongly advise against writing each record in a
 /// separate vault, but instead group them into a limited number of different vaults.**
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Location {
     Generic { vault_path: Vec<u8>, record_path: Vec<u8> },
     Counter { vault_path: Vec<u8>, counter: usize },
@@ -58,6 +67,17 @@ impl Location {
     pub const fn const_counter(vault_path: Vec<u8>, counter: usize) -> Self {
         Self::Counter { vault_path, counter }
     }
+
+    /// Creates a location for an intermediate secret of a chain of procedures. The records of all ephemeral locations
+    /// are revoked and garbage collected once the chain has finished, whether it succeeded or failed.
+    pub fn ephemeral<R: Into<Vec<u8>>>(record_path: R) -> Self {
+        Self::generic(EPHEMERAL_VAULT_PATH, record_path)
+    }
+
+    /// Checks if the location was created with [`Location::ephemeral`].
+    pub fn is_ephemeral(&self) -> bool {
+        self.vault_path() == EPHEMERAL_VAULT_PATH
+    }
 }
 
 impl AsRef<Location> for Location {
@@ -66,6 +86,29 @@ impl AsRef<Location> for Location {
     }
 }
 
+/// Metadata of a record, that is stored encrypted next to the record in its vault.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordMetadata {
+    /// Creation time in seconds since the unix epoch.
+    pub created: u64,
+    /// Time of the last use by a procedure in seconds since the unix epoch.
+    pub last_used: Option<u64>,
+    /// Name of the [`StrongholdProcedure`][crate::procedures::StrongholdProcedure] that created the record. `None` if
+    /// the record was written directly.
+    pub origin: Option<String>,
+    /// Location of the secret that the record was derived from.
+    pub source: Option<Location>,
+}
+
+/// A record of a vault as it is listed by [`Stronghold::list_records`][crate::Stronghold::list_records].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordInfo {
+    pub id: RecordId,
+    pub hint: RecordHint,
+    /// `None` for records that were written before metadata was recorded.
+    pub metadata: Option<RecordMetadata>,
+}
+
 /// Policy options for modifying an entire Stronghold.  Must be specified on creation.
 ///
 /// note:
@@ -76,5 +119,72 @@ pub enum StrongholdFlags {
 }
 
 /// Policy options for for a specific vault.  Must be specified on creation.
-#[derive(Clone, Debug)]
-pub enum VaultFlags {}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VaultFlags {
+    /// Restricts the kinds of procedures that may access the records of the vault.  All kinds are allowed if the flag
+    /// is not set.  Leaving out [`ProcedureKind::Copy`] prevents records from being copied with `CopyRecord`.
+    AllowedProcedures(Vec<ProcedureKind>),
+    /// Maximum number of records that the vault may hold.
+    MaxRecords(usize),
+    /// Records can not be overwritten once they were written.
+    WriteOnce,
+}
+
+/// Policy of a vault that is assembled from its [`VaultFlags`].  If a flag is given multiple times, the last one
+/// applies.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VaultPolicy {
+    pub allowed_procedures: Option<Vec<ProcedureKind>>,
+    pub max_records: Option<usize>,
+    pub write_once: bool,
+}
+
+impl VaultPolicy {
+    /// Creates the policy for the given flags.
+    pub fn new(flags: Vec<VaultFlags>) -> Self {
+        let mut policy = Self::default();
+        for flag in flags {
+            match flag {
+                VaultFlags::AllowedProcedures(kinds) => policy.allowed_procedures = Some(kinds),
+                VaultFlags::MaxRecords(max) => policy.max_records = Some(max),
+                VaultFlags::WriteOnce => policy.write_once = true,
+            }
+        }
+        policy
+    }
+
+    /// Checks if procedures of the given kind may access the records of the vault.
+    pub fn check_kind(&self, kind: ProcedureKind) -> Result<(), PolicyError> {
+        match &self.allowed_procedures {
+            Some(kinds) if !kinds.contains(&kind) => Err(PolicyError::ProcedureNotAllowed(kind)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks if a record may be written, given whether it already exists and the number of records in the vault.
+    pub fn check_write(&self, exists: bool, count: usize) -> Result<(), PolicyError> {
+        if exists {
+            if self.write_once {
+                return Err(PolicyError::WriteOnce);
+            }
+        } else if let Some(max) = self.max_records {
+            if count >= max {
+                return Err(PolicyError::MaxRecords(max));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Violation of a [`VaultPolicy`].
+#[derive(DeriveError, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PolicyError {
+    #[error("procedures of kind {0:?} are not allowed on the vault")]
+    ProcedureNotAllowed(ProcedureKind),
+
+    #[error("vault is limited to {0} records")]
+    MaxRecords(usize),
+
+    #[error("record in write-once vault can not be overwritten")]
+    WriteOnce,
+}
@@ -18,6 +18,12 @@
 //! The current version of the format is using X25519 together with an ephemeral
 //! key to derive a shared key for the symmetric XChaCha20 cipher and uses the
 //! Poly1305 message authentication algorithm.
+//!
+//! Since version 3 the magic and version bytes are followed by an extensible list
+//! of header fields: the key derivation (e.g. the Argon2id salt and cost parameters),
+//! the compression codec, the creation time, the contained client ids and an optional
+//! label. The header can be read without the key and is authenticated together with
+//! the associated data. Version 2 snapshots can still be read.
 
 //! Future versions, when the demands for larger snapshot sizes and/or random
 //! access is desired, might consider encrypting smaller chunks (B-trees?) or
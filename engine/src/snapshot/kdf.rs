@@ -1,8 +1,157 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use argon2::{Algorithm, Argon2, Params, Version};
+use thiserror::Error as DeriveError;
+
+/// Length of the salt used with [`argon2id_kdf`].
+pub const ARGON2_SALT_LEN: usize = 32;
+
+/// Maximal memory size in KiB accepted by [`argon2id_kdf`] (256 MiB).
+pub const ARGON2_MAX_MEMORY_COST: u32 = 256 * 1024;
+
+/// Maximal number of iterations accepted by [`argon2id_kdf`].
+pub const ARGON2_MAX_TIME_COST: u32 = 16;
+
+/// Maximal degree of parallelism accepted by [`argon2id_kdf`].
+pub const ARGON2_MAX_PARALLELISM: u32 = 16;
+
 /// a wrapper around the [`HMAC_SHA256`][crypto::macs::hmac::HMAC_SHA256] function used to derive a hash from a given
 /// password.
 pub fn naive_kdf(password: &[u8], salt: &[u8; 32], key: &mut [u8; 32]) {
     crypto::macs::hmac::HMAC_SHA256(password, salt, key);
 }
+
+#[derive(Debug, DeriveError)]
+#[error("key derivation failed: {0}")]
+pub struct KdfError(String);
+
+/// Cost parameters for [`argon2id_kdf`].
+///
+/// The parameters are stored alongside the salt in the snapshot header, so raising them for new snapshots does not
+/// affect snapshots that were written with lower costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Memory size in KiB.
+    pub memory_cost: u32,
+    /// Number of iterations.
+    pub time_cost: u32,
+    /// Degree of parallelism.
+    pub parallelism: u32,
+}
+
+impl Argon2Params {
+    /// Checks that the costs do not exceed [`ARGON2_MAX_MEMORY_COST`], [`ARGON2_MAX_TIME_COST`] and
+    /// [`ARGON2_MAX_PARALLELISM`]. The parameters of a snapshot are read from its header before the snapshot can be
+    /// authenticated, so without these bounds a crafted header could make the key derivation exhaust memory or run
+    /// for hours.
+    pub fn check_bounds(&self) -> Result<(), KdfError> {
+        if self.memory_cost > ARGON2_MAX_MEMORY_COST {
+            return Err(KdfError(format!(
+                "memory cost {} KiB exceeds the maximum",
+                self.memory_cost
+            )));
+        }
+        if self.time_cost > ARGON2_MAX_TIME_COST {
+            return Err(KdfError(format!("time cost {} exceeds the maximum", self.time_cost)));
+        }
+        if self.parallelism > ARGON2_MAX_PARALLELISM {
+            return Err(KdfError(format!(
+                "parallelism {} exceeds the maximum",
+                self.parallelism
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Argon2Params {
+    /// The minimal configuration recommended by OWASP: 19 MiB of memory, 2 iterations and a parallelism of 1.
+    fn default() -> Self {
+        Self {
+            memory_cost: 19 * 1024,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// Derives a 256 bit key from the given password with the memory-hard Argon2id function.
+pub fn argon2id_kdf(
+    password: &[u8],
+    salt: &[u8; ARGON2_SALT_LEN],
+    params: &Argon2Params,
+    key: &mut [u8; 32],
+) -> Result<(), KdfError> {
+    params.check_bounds()?;
+    let params = Params::new(
+        params.memory_cost,
+        params.time_cost,
+        params.parallelism,
+        Some(key.len()),
+    )
+    .map_err(|e| KdfError(e.to_string()))?;
+
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password, salt, key)
+        .map_err(|e| KdfError(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_argon2id_kdf() {
+        let params = Argon2Params {
+            memory_cost: 64,
+            time_cost: 1,
+            parallelism: 1,
+        };
+        let salt = [1u8; ARGON2_SALT_LEN];
+
+        let mut k0 = [0u8; 32];
+        let mut k1 = [0u8; 32];
+        argon2id_kdf(b"password", &salt, &params, &mut k0).unwrap();
+        argon2id_kdf(b"password", &salt, &params, &mut k1).unwrap();
+        assert_eq!(k0, k1);
+
+        argon2id_kdf(b"password", &[2u8; ARGON2_SALT_LEN], &params, &mut k1).unwrap();
+        assert_ne!(k0, k1);
+
+        let params = Argon2Params { time_cost: 2, ..params };
+        argon2id_kdf(b"password", &salt, &params, &mut k1).unwrap();
+        assert_ne!(k0, k1);
+    }
+
+    #[test]
+    fn test_argon2id_invalid_params() {
+        let params = Argon2Params {
+            memory_cost: 64,
+            time_cost: 0,
+            parallelism: 1,
+        };
+        let mut key = [0u8; 32];
+        assert!(argon2id_kdf(b"password", &[0u8; ARGON2_SALT_LEN], &params, &mut key).is_err());
+
+        let params = Argon2Params {
+            memory_cost: ARGON2_MAX_MEMORY_COST + 1,
+            time_cost: 1,
+            parallelism: 1,
+        };
+        assert!(params.check_bounds().is_err());
+        assert!(argon2id_kdf(b"password", &[0u8; ARGON2_SALT_LEN], &params, &mut key).is_err());
+        let params = Argon2Params {
+            memory_cost: 64,
+            time_cost: ARGON2_MAX_TIME_COST + 1,
+            parallelism: 1,
+        };
+        assert!(params.check_bounds().is_err());
+        let params = Argon2Params {
+            memory_cost: 64,
+            time_cost: 1,
+            parallelism: ARGON2_MAX_PARALLELISM + 1,
+        };
+        assert!(params.check_bounds().is_err());
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     fs::{rename, File, OpenOptions},
     io::{Read, Write},
     path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use crypto::{
@@ -14,15 +15,24 @@ use crypto::{
     utils::rand,
 };
 use thiserror::Error as DeriveError;
-
-use crate::snapshot::{compress, decompress};
+use zeroize::Zeroizing;
+
+use crate::{
+    snapshot::{
+        compress, decompress,
+        kdf::{argon2id_kdf, Argon2Params, ARGON2_SALT_LEN},
+    },
+    vault::ClientId,
+};
 
 /// Magic bytes (bytes 0-4 in a snapshot file) aka PARTI
 pub const MAGIC: [u8; 5] = [0x50, 0x41, 0x52, 0x54, 0x49];
 
 /// Current version bytes (bytes 5-6 in a snapshot file)
-pub const VERSION: [u8; 2] = [0x2, 0x0];
-// pub const OLD_VERSION: [u8; 2] = [0x2, 0x0];
+pub const VERSION: [u8; 2] = [0x3, 0x0];
+
+/// Version bytes of the previous format, which is still supported for reading.
+pub const OLD_VERSION: [u8; 2] = [0x2, 0x0];
 
 /// Key size for the ephemeral key
 const KEY_SIZE: usize = 32;
@@ -34,6 +44,22 @@ const NONCE_SIZE: usize = XChaCha20Poly1305::NONCE_LENGTH;
 /// Nonce type alias
 pub type Nonce = [u8; NONCE_SIZE];
 
+/// Identifier of [`KeyDerivation::None`] in the snapshot header.
+const KDF_NONE: u8 = 0x0;
+/// Identifier of [`KeyDerivation::Argon2id`] in the snapshot header.
+const KDF_ARGON2ID: u8 = 0x1;
+
+/// Tags of the fields in the snapshot header. Readers skip fields with an unknown tag, so new fields can be added
+/// without breaking older readers.
+const FIELD_KDF: u8 = 0x1;
+const FIELD_COMPRESSION: u8 = 0x2;
+const FIELD_CREATED_AT: u8 = 0x3;
+const FIELD_CLIENT_IDS: u8 = 0x4;
+const FIELD_LABEL: u8 = 0x5;
+
+/// Length of an encoded [`ClientId`].
+const CLIENT_ID_LEN: usize = 24;
+
 #[derive(Debug, DeriveError)]
 pub enum ReadError {
     #[error("I/O error: {0}")]
@@ -47,6 +73,9 @@ pub enum ReadError {
 
     #[error("unsupported version: expected `{expected:?}`, found `{found:?}`")]
     UnsupportedVersion { expected: [u8; 2], found: [u8; 2] },
+
+    #[error("snapshot key is not derived from a password")]
+    NoPasswordKdf,
 }
 
 #[derive(Debug, DeriveError)]
@@ -61,12 +90,292 @@ pub enum WriteError {
     CorruptedData(String),
 }
 
+/// Secret that protects a snapshot.
+#[derive(Clone)]
+pub enum SnapshotKey {
+    /// A raw 256 bit key that is used as is.
+    Key(Key),
+
+    /// A password that the key is derived from with Argon2id. The parameters only apply when writing a snapshot,
+    /// on read the salt and parameters recorded in the snapshot header are used.
+    Password(Zeroizing<Vec<u8>>, Argon2Params),
+}
+
+/// Describes how the snapshot key was obtained. It is stored in the header of the snapshot, so that a snapshot
+/// protected by a password can be opened with nothing but the password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDerivation {
+    /// The key was provided by the caller.
+    None,
+
+    /// The key was derived from a password with [`argon2id_kdf`].
+    Argon2id {
+        salt: [u8; ARGON2_SALT_LEN],
+        params: Argon2Params,
+    },
+}
+
+impl KeyDerivation {
+    /// Encodes the key derivation as it is written into the snapshot header.
+    fn to_bytes(self) -> Vec<u8> {
+        match self {
+            KeyDerivation::None => vec![KDF_NONE],
+            KeyDerivation::Argon2id { salt, params } => {
+                let mut bytes = vec![KDF_ARGON2ID];
+                bytes.extend_from_slice(&params.memory_cost.to_le_bytes());
+                bytes.extend_from_slice(&params.time_cost.to_le_bytes());
+                bytes.extend_from_slice(&params.parallelism.to_le_bytes());
+                bytes.extend_from_slice(&salt);
+                bytes
+            }
+        }
+    }
+
+    /// Decodes the key derivation from the value of its header field.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, ReadError> {
+        match bytes {
+            [KDF_NONE] => Ok(KeyDerivation::None),
+            [KDF_ARGON2ID, rest @ ..] if rest.len() == 3 * 4 + ARGON2_SALT_LEN => {
+                let u32_at = |i: usize| u32::from_le_bytes(rest[i * 4..(i + 1) * 4].try_into().unwrap());
+                let params = Argon2Params {
+                    memory_cost: u32_at(0),
+                    time_cost: u32_at(1),
+                    parallelism: u32_at(2),
+                };
+                params
+                    .check_bounds()
+                    .map_err(|e| ReadError::CorruptedContent(e.to_string()))?;
+                let salt = rest[3 * 4..].try_into().unwrap();
+                Ok(KeyDerivation::Argon2id { salt, params })
+            }
+            _ => Err(ReadError::CorruptedContent("invalid key derivation".into())),
+        }
+    }
+}
+
+/// Compression that is applied to the plaintext before it is encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Lz4,
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Compression::None => 0x0,
+            Compression::Lz4 => 0x1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, ReadError> {
+        match id {
+            0x0 => Ok(Compression::None),
+            0x1 => Ok(Compression::Lz4),
+            id => Err(ReadError::CorruptedContent(format!("unknown compression `{}`", id))),
+        }
+    }
+}
+
+/// Descriptive fields of the snapshot header, that are provided by the writer of the snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    /// The clients of which the state is contained in the snapshot.
+    pub client_ids: Vec<ClientId>,
+
+    /// An optional label chosen by the user.
+    pub label: Option<String>,
+}
+
+/// The header of a snapshot. It is stored in plain in front of the ciphertext and can be read without the snapshot
+/// key, but is authenticated together with the associated data when the snapshot is decrypted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    /// The version of the snapshot format.
+    pub version: [u8; 2],
+
+    /// How the snapshot key was obtained.
+    pub kdf: KeyDerivation,
+
+    /// The compression applied to the plaintext.
+    pub compression: Compression,
+
+    /// Creation time in seconds since the unix epoch. Not recorded in version 2.0 snapshots.
+    pub created_at: Option<u64>,
+
+    /// Fields provided by the writer. Always empty for version 2.0 snapshots.
+    pub info: SnapshotInfo,
+}
+
+impl SnapshotHeader {
+    /// Creates the header for a new snapshot, stamped with the current time.
+    fn new(kdf: KeyDerivation, compression: Compression, info: SnapshotInfo) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            version: VERSION,
+            kdf,
+            compression,
+            created_at: Some(created_at),
+            info,
+        }
+    }
+
+    /// The header of version 2.0 snapshots, which contains no fields. Snapshot files of this version are always
+    /// compressed.
+    fn old_version() -> Self {
+        Self {
+            version: OLD_VERSION,
+            kdf: KeyDerivation::None,
+            compression: Compression::Lz4,
+            created_at: None,
+            info: SnapshotInfo::default(),
+        }
+    }
+
+    /// Encodes the header fields as a list of `tag || length (u32 LE) || value` entries.
+    fn encode_fields(&self) -> Vec<u8> {
+        fn push_field(buf: &mut Vec<u8>, tag: u8, value: &[u8]) {
+            buf.push(tag);
+            buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            buf.extend_from_slice(value);
+        }
+
+        let mut fields = Vec::new();
+        push_field(&mut fields, FIELD_KDF, &self.kdf.to_bytes());
+        push_field(&mut fields, FIELD_COMPRESSION, &[self.compression.id()]);
+        if let Some(created_at) = self.created_at {
+            push_field(&mut fields, FIELD_CREATED_AT, &created_at.to_le_bytes());
+        }
+        if !self.info.client_ids.is_empty() {
+            let ids: Vec<u8> = self
+                .info
+                .client_ids
+                .iter()
+                .flat_map(|id| id.as_ref().to_vec())
+                .collect();
+            push_field(&mut fields, FIELD_CLIENT_IDS, &ids);
+        }
+        if let Some(label) = &self.info.label {
+            push_field(&mut fields, FIELD_LABEL, label.as_bytes());
+        }
+        fields
+    }
+
+    /// Decodes the header fields of a version 3 snapshot. Fields with an unknown tag are skipped.
+    fn decode_fields(mut fields: &[u8]) -> Result<Self, ReadError> {
+        let corrupted = |field: &str| ReadError::CorruptedContent(format!("invalid header field: {}", field));
+
+        let mut kdf = None;
+        let mut header = SnapshotHeader {
+            version: VERSION,
+            kdf: KeyDerivation::None,
+            compression: Compression::None,
+            created_at: None,
+            info: SnapshotInfo::default(),
+        };
+
+        while !fields.is_empty() {
+            if fields.len() < 5 {
+                return Err(corrupted("truncated field"));
+            }
+            let tag = fields[0];
+            let len = u32::from_le_bytes(fields[1..5].try_into().unwrap()) as usize;
+            if fields.len() - 5 < len {
+                return Err(corrupted("truncated field"));
+            }
+            let value = &fields[5..5 + len];
+            fields = &fields[5 + len..];
+
+            match tag {
+                FIELD_KDF => kdf = Some(KeyDerivation::from_bytes(value)?),
+                FIELD_COMPRESSION => match value {
+                    [id] => header.compression = Compression::from_id(*id)?,
+                    _ => return Err(corrupted("compression")),
+                },
+                FIELD_CREATED_AT => {
+                    let created_at = value.try_into().map_err(|_| corrupted("creation time"))?;
+                    header.created_at = Some(u64::from_le_bytes(created_at));
+                }
+                FIELD_CLIENT_IDS => {
+                    if !value.len().is_multiple_of(CLIENT_ID_LEN) {
+                        return Err(corrupted("client ids"));
+                    }
+                    header.info.client_ids = value
+                        .chunks(CLIENT_ID_LEN)
+                        .map(|id| ClientId::load(id).map_err(|_| corrupted("client ids")))
+                        .collect::<Result<_, _>>()?;
+                }
+                FIELD_LABEL => {
+                    let label = String::from_utf8(value.to_vec()).map_err(|_| corrupted("label"))?;
+                    header.info.label = Some(label);
+                }
+                _ => {}
+            }
+        }
+
+        header.kdf = kdf.ok_or_else(|| corrupted("missing key derivation"))?;
+        Ok(header)
+    }
+}
+
 /// Encrypt the opaque plaintext bytestring using the specified [`Key`] and optional associated data
 /// and writes the ciphertext to the specifed output
 pub fn write<O: Write>(plain: &[u8], output: &mut O, key: &Key, associated_data: &[u8]) -> Result<(), WriteError> {
-    // write magic and version bytes
-    output.write_all(&MAGIC)?;
-    output.write_all(&VERSION)?;
+    let header = SnapshotHeader::new(KeyDerivation::None, Compression::None, SnapshotInfo::default());
+    write_with_header(plain, output, key, &header, associated_data)
+}
+
+/// Encrypt the opaque plaintext bytestring with a key derived from `password` using Argon2id with a fresh random salt
+/// and the given cost `params`. The salt and the parameters are recorded in the snapshot header.
+pub fn write_with_password<O: Write>(
+    plain: &[u8],
+    output: &mut O,
+    password: &[u8],
+    params: &Argon2Params,
+    associated_data: &[u8],
+) -> Result<(), WriteError> {
+    let (key, kdf) = derive_key(&SnapshotKey::Password(password.to_vec().into(), *params))?;
+    let header = SnapshotHeader::new(kdf, Compression::None, SnapshotInfo::default());
+    write_with_header(plain, output, &key, &header, associated_data)
+}
+
+/// Resolves the [`SnapshotKey`] into the encryption key, generating a fresh salt for password derived keys.
+fn derive_key(key: &SnapshotKey) -> Result<(Zeroizing<Key>, KeyDerivation), WriteError> {
+    match key {
+        SnapshotKey::Key(key) => Ok((Zeroizing::new(*key), KeyDerivation::None)),
+        SnapshotKey::Password(password, params) => {
+            let mut salt = [0u8; ARGON2_SALT_LEN];
+            rand::fill(&mut salt).map_err(|e| WriteError::GenerateRandom(format!("{}", e)))?;
+
+            let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+            argon2id_kdf(password, &salt, params, &mut key).map_err(|e| WriteError::CorruptedData(e.to_string()))?;
+
+            Ok((key, KeyDerivation::Argon2id { salt, params: *params }))
+        }
+    }
+}
+
+fn write_with_header<O: Write>(
+    plain: &[u8],
+    output: &mut O,
+    key: &Key,
+    header: &SnapshotHeader,
+    associated_data: &[u8],
+) -> Result<(), WriteError> {
+    // write magic and version bytes, followed by the length prefixed header fields.
+    let fields = header.encode_fields();
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&VERSION);
+    header.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+    header.extend_from_slice(&fields);
+    output.write_all(&header)?;
+
+    // the header is authenticated together with the associated data.
+    header.extend_from_slice(associated_data);
 
     // create ephemeral key pair.
     let ephemeral_key = x25519::SecretKey::generate().map_err(|e| WriteError::GenerateRandom(format!("{}", e)))?;
@@ -80,11 +389,11 @@ pub fn write<O: Write>(plain: &[u8], output: &mut O, key: &Key, associated_data:
     output.write_all(&ephemeral_pk_bytes)?;
 
     // secret key now expects an array
-    let mut key_bytes = [0u8; x25519::SECRET_KEY_LENGTH];
+    let mut key_bytes = Zeroizing::new([0u8; x25519::SECRET_KEY_LENGTH]);
     key_bytes.clone_from_slice(key);
 
     // get `x25519` secret key from public key.
-    let pk = x25519::SecretKey::from_bytes(key_bytes).public_key();
+    let pk = x25519::SecretKey::from_bytes(*key_bytes).public_key();
 
     let pk_bytes = pk.to_bytes();
 
@@ -107,7 +416,7 @@ pub fn write<O: Write>(plain: &[u8], output: &mut O, key: &Key, associated_data:
     let mut ct = vec![0; plain.len()];
 
     // decrypt the plain text into the ciphertext buffer.
-    XChaCha20Poly1305::try_encrypt(&shared.to_bytes(), &nonce, associated_data, plain, &mut ct, &mut tag)
+    XChaCha20Poly1305::try_encrypt(&shared.to_bytes(), &nonce, &header, plain, &mut ct, &mut tag)
         .map_err(|e| WriteError::CorruptedData(format!("Encryption failed: {}", e)))?;
 
     // write tag and ciphertext into the output.
@@ -120,23 +429,50 @@ pub fn write<O: Write>(plain: &[u8], output: &mut O, key: &Key, associated_data:
 /// Read ciphertext from the input, decrypts it using the specified key and the associated data
 /// specified during encryption and returns the plaintext
 pub fn read<I: Read>(input: &mut I, key: &Key, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
-    // check the header for structure.
-    check_header(input)?;
+    let (_, authenticated) = read_authenticated_header(input, associated_data)?;
+    read_with_key(input, key, &authenticated)
+}
+
+/// Read ciphertext from the input and decrypts it with a key derived from `password`, using the key derivation
+/// recorded in the snapshot header. Fails with [`ReadError::NoPasswordKdf`] if the snapshot was not written with
+/// [`write_with_password`].
+pub fn read_with_password<I: Read>(
+    input: &mut I,
+    password: &[u8],
+    associated_data: &[u8],
+) -> Result<Vec<u8>, ReadError> {
+    let (header, authenticated) = read_authenticated_header(input, associated_data)?;
+    let key = password_key(&header, password)?;
+    read_with_key(input, &key, &authenticated)
+}
 
+/// Derives the snapshot key from the password with the key derivation recorded in the header.
+fn password_key(header: &SnapshotHeader, password: &[u8]) -> Result<Zeroizing<Key>, ReadError> {
+    match header.kdf {
+        KeyDerivation::Argon2id { salt, params } => {
+            let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+            argon2id_kdf(password, &salt, &params, &mut key).map_err(|e| ReadError::CorruptedContent(e.to_string()))?;
+            Ok(key)
+        }
+        KeyDerivation::None => Err(ReadError::NoPasswordKdf),
+    }
+}
+
+fn read_with_key<I: Read>(input: &mut I, key: &Key, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
     // create ephemeral private key.
     let mut ephemeral_pk = [0; x25519::PUBLIC_KEY_LENGTH];
     // get ephemeral private key from input.
     input.read_exact(&mut ephemeral_pk)?;
 
     // creating the secret key now expects an array
-    let mut key_bytes = [0u8; x25519::SECRET_KEY_LENGTH];
+    let mut key_bytes = Zeroizing::new([0u8; x25519::SECRET_KEY_LENGTH]);
     key_bytes.clone_from_slice(key);
 
     // derive public key from ephemeral private key
     let ephemeral_pk = x25519::PublicKey::from_bytes(ephemeral_pk);
 
     // get x25519 key pair from ephemeral private key.
-    let sk = x25519::SecretKey::from_bytes(key_bytes);
+    let sk = x25519::SecretKey::from_bytes(*key_bytes);
     let pk = sk.public_key();
 
     // diffie hellman to create the shared secret.
@@ -175,9 +511,47 @@ pub fn read<I: Read>(input: &mut I, key: &Key, associated_data: &[u8]) -> Result
 /// filename with a salted suffix). This is currently known to be problematic if the path is a
 /// symlink and/or if the target path resides in a directory without user write permission.
 pub fn write_to(plain: &[u8], path: &Path, key: &Key, associated_data: &[u8]) -> Result<(), WriteError> {
+    write_snapshot(
+        plain,
+        path,
+        &SnapshotKey::Key(*key),
+        SnapshotInfo::default(),
+        associated_data,
+    )
+}
+
+/// Atomically encrypt and [`write_with_password`](fn.write_with_password.html) the specified plaintext to the
+/// specified path. See [`write_to`] for details.
+pub fn write_to_with_password(
+    plain: &[u8],
+    path: &Path,
+    password: &[u8],
+    params: &Argon2Params,
+    associated_data: &[u8],
+) -> Result<(), WriteError> {
+    write_snapshot(
+        plain,
+        path,
+        &SnapshotKey::Password(password.to_vec().into(), *params),
+        SnapshotInfo::default(),
+        associated_data,
+    )
+}
+
+/// Atomically compresses, encrypts and writes the specified plaintext to the specified path, recording the `info`
+/// in the snapshot header. See [`write_to`] for details.
+pub fn write_snapshot(
+    plain: &[u8],
+    path: &Path,
+    key: &SnapshotKey,
+    info: SnapshotInfo,
+    associated_data: &[u8],
+) -> Result<(), WriteError> {
     // TODO: if path exists and is a symlink, resolve it and then append the salt
     // TODO: if the sibling tempfile isn't writeable (e.g. directory permissions), write to
 
+    let (key, kdf) = derive_key(key)?;
+    let header = SnapshotHeader::new(kdf, Compression::Lz4, info);
     let compressed_plain = compress(plain);
 
     let mut salt = [0u8; 6];
@@ -189,7 +563,7 @@ pub fn write_to(plain: &[u8], path: &Path, key: &Key, associated_data: &[u8]) ->
     let tmp = Path::new(&s);
 
     let mut f = OpenOptions::new().write(true).create_new(true).open(tmp)?;
-    write(&compressed_plain, &mut f, key, associated_data)?;
+    write_with_header(&compressed_plain, &mut f, &key, &header, associated_data)?;
     f.sync_all()?;
 
     rename(tmp, path)?;
@@ -199,11 +573,54 @@ pub fn write_to(plain: &[u8], path: &Path, key: &Key, associated_data: &[u8]) ->
 
 /// [`read`](fn.read.html) and decrypt the ciphertext from the specified path
 pub fn read_from(path: &Path, key: &Key, associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
+    read_snapshot(path, &SnapshotKey::Key(*key), associated_data).map(|(_, pt)| pt)
+}
+
+/// [`read_with_password`](fn.read_with_password.html) and decrypt the ciphertext from the specified path
+pub fn read_from_with_password(path: &Path, password: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, ReadError> {
+    // the parameters are taken from the snapshot header.
+    let key = SnapshotKey::Password(password.to_vec().into(), Argon2Params::default());
+    read_snapshot(path, &key, associated_data).map(|(_, pt)| pt)
+}
+
+/// Reads, decrypts and decompresses the snapshot from the specified path. Returns the snapshot header together with
+/// the plaintext.
+pub fn read_snapshot(
+    path: &Path,
+    key: &SnapshotKey,
+    associated_data: &[u8],
+) -> Result<(SnapshotHeader, Vec<u8>), ReadError> {
     let mut f: File = OpenOptions::new().read(true).open(path)?;
     check_min_file_len(&mut f)?;
-    let pt = read(&mut f, key, associated_data)?;
 
-    decompress(&pt).map_err(|e| ReadError::CorruptedContent(format!("Decompression failed: {}", e)))
+    let (header, authenticated) = read_authenticated_header(&mut f, associated_data)?;
+    let key = match key {
+        SnapshotKey::Key(key) => Zeroizing::new(*key),
+        SnapshotKey::Password(password, _) => password_key(&header, password)?,
+    };
+    let pt = read_with_key(&mut f, &key, &authenticated)?;
+
+    let pt = match header.compression {
+        Compression::None => pt,
+        Compression::Lz4 => {
+            decompress(&pt).map_err(|e| ReadError::CorruptedContent(format!("Decompression failed: {}", e)))?
+        }
+    };
+
+    Ok((header, pt))
+}
+
+/// Reads the header of the snapshot at the specified path without decrypting the snapshot. Note that the header is
+/// only authenticated when the snapshot is decrypted.
+pub fn read_header_from(path: &Path) -> Result<SnapshotHeader, ReadError> {
+    let mut f: File = OpenOptions::new().read(true).open(path)?;
+    check_min_file_len(&mut f)?;
+    read_header(&mut f)
+}
+
+/// Reads the header of a snapshot from the input, see [`read_header_from`].
+pub fn read_header<I: Read>(input: &mut I) -> Result<SnapshotHeader, ReadError> {
+    read_authenticated_header(input, &[]).map(|(header, _)| header)
 }
 
 fn check_min_file_len(input: &mut File) -> Result<(), ReadError> {
@@ -215,8 +632,12 @@ fn check_min_file_len(input: &mut File) -> Result<(), ReadError> {
     }
 }
 
-/// Checks the header for a specific structure; explicitly the magic and version bytes.
-fn check_header<I: Read>(input: &mut I) -> Result<(), ReadError> {
+/// Checks the header for a specific structure; explicitly the magic and version bytes, and reads the header fields
+/// of version 3 snapshots. Returns the header together with the data that is authenticated by the cipher.
+fn read_authenticated_header<I: Read>(
+    input: &mut I,
+    associated_data: &[u8],
+) -> Result<(SnapshotHeader, Vec<u8>), ReadError> {
     // check the magic bytes
     let mut magic = [0u8; 5];
     input.read_exact(&mut magic)?;
@@ -228,14 +649,37 @@ fn check_header<I: Read>(input: &mut I) -> Result<(), ReadError> {
     let mut version = [0u8; 2];
     input.read_exact(&mut version)?;
 
-    if version != VERSION {
-        return Err(ReadError::UnsupportedVersion {
+    match version {
+        // the old format has no header fields and authenticates only the associated data.
+        OLD_VERSION => Ok((SnapshotHeader::old_version(), associated_data.to_vec())),
+        VERSION => {
+            let mut len = [0u8; 4];
+            input.read_exact(&mut len)?;
+
+            // read incrementally, so that a corrupted length does not allocate a huge buffer.
+            let mut fields = Vec::new();
+            input
+                .by_ref()
+                .take(u32::from_le_bytes(len) as u64)
+                .read_to_end(&mut fields)?;
+            if fields.len() != u32::from_le_bytes(len) as usize {
+                return Err(ReadError::CorruptedContent("truncated header".into()));
+            }
+            let header = SnapshotHeader::decode_fields(&fields)?;
+
+            let mut authenticated = MAGIC.to_vec();
+            authenticated.extend_from_slice(&VERSION);
+            authenticated.extend_from_slice(&len);
+            authenticated.extend_from_slice(&fields);
+            authenticated.extend_from_slice(associated_data);
+
+            Ok((header, authenticated))
+        }
+        _ => Err(ReadError::UnsupportedVersion {
             expected: VERSION,
             found: version,
-        });
+        }),
     }
-
-    Ok(())
 }
 
 #[cfg(test)]
@@ -328,6 +772,161 @@ mod test {
         assert_eq!(bs0, bs1);
     }
 
+    fn test_params() -> Argon2Params {
+        Argon2Params {
+            memory_cost: 64,
+            time_cost: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn test_password_snapshot() {
+        let f = tempfile::tempdir().unwrap();
+        let mut pb = f.into_path();
+        pb.push("snapshot");
+
+        let bs0 = random_bytestring();
+        let ad = random_bytestring();
+
+        write_to_with_password(&bs0, &pb, b"password", &test_params(), &ad).unwrap();
+        let bs1 = read_from_with_password(&pb, b"password", &ad).unwrap();
+        assert_eq!(bs0, bs1);
+
+        assert!(read_from_with_password(&pb, b"wrong password", &ad).is_err());
+    }
+
+    #[test]
+    fn test_password_read_without_kdf() {
+        let key: Key = random_key();
+        let mut buf = Vec::new();
+        write(&random_bytestring(), &mut buf, &key, &[]).unwrap();
+
+        assert!(matches!(
+            read_with_password(&mut buf.as_slice(), b"password", &[]),
+            Err(ReadError::NoPasswordKdf)
+        ));
+    }
+
+    #[test]
+    fn test_password_header_is_authenticated() {
+        let bs0 = random_bytestring();
+        let mut buf = Vec::new();
+        write_with_password(&bs0, &mut buf, b"password", &test_params(), &[]).unwrap();
+
+        // the key derived from the original header decrypts the snapshot.
+        let (salt, params) = match read_header(&mut buf.as_slice()).unwrap().kdf {
+            KeyDerivation::Argon2id { salt, params } => (salt, params),
+            KeyDerivation::None => panic!("missing key derivation"),
+        };
+        let mut key: Key = [0u8; KEY_SIZE];
+        argon2id_kdf(b"password", &salt, &params, &mut key).unwrap();
+        assert_eq!(read(&mut buf.as_slice(), &key, &[]).unwrap(), bs0);
+
+        // raising the time cost in the header is detected, even if the right key is used.
+        let time_cost_offset = MAGIC.len() + VERSION.len() + 4 + 5 + 1 + 4;
+        buf[time_cost_offset] += 1;
+        assert_eq!(
+            read_header(&mut buf.as_slice()).unwrap().kdf,
+            KeyDerivation::Argon2id {
+                salt,
+                params: Argon2Params {
+                    time_cost: params.time_cost + 1,
+                    ..params
+                }
+            }
+        );
+        assert!(read(&mut buf.as_slice(), &key, &[]).is_err());
+    }
+
+    #[test]
+    fn test_password_header_exceeding_costs() {
+        let header = SnapshotHeader::new(
+            KeyDerivation::Argon2id {
+                salt: [0u8; ARGON2_SALT_LEN],
+                params: Argon2Params {
+                    memory_cost: u32::MAX,
+                    ..test_params()
+                },
+            },
+            Compression::None,
+            SnapshotInfo::default(),
+        );
+        let fields = header.encode_fields();
+        assert!(matches!(
+            SnapshotHeader::decode_fields(&fields),
+            Err(ReadError::CorruptedContent(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_header() {
+        let f = tempfile::tempdir().unwrap();
+        let mut pb = f.into_path();
+        pb.push("snapshot");
+
+        let key: Key = random_key();
+        let bs0 = random_bytestring();
+        let info = SnapshotInfo {
+            client_ids: vec![ClientId::load(&[1u8; 24]).unwrap(), ClientId::load(&[2u8; 24]).unwrap()],
+            label: Some("my snapshot".into()),
+        };
+
+        write_snapshot(&bs0, &pb, &SnapshotKey::Key(key), info.clone(), &[]).unwrap();
+
+        let header = read_header_from(&pb).unwrap();
+        assert_eq!(header.version, VERSION);
+        assert_eq!(header.kdf, KeyDerivation::None);
+        assert_eq!(header.compression, Compression::Lz4);
+        assert!(header.created_at.is_some());
+        assert_eq!(header.info, info);
+
+        let (header1, bs1) = read_snapshot(&pb, &SnapshotKey::Key(key), &[]).unwrap();
+        assert_eq!(header1, header);
+        assert_eq!(bs0, bs1);
+    }
+
+    #[test]
+    fn test_snapshot_header_unknown_field() {
+        let key: Key = random_key();
+        let bs0 = random_bytestring();
+        let header = SnapshotHeader::new(KeyDerivation::None, Compression::None, SnapshotInfo::default());
+
+        // a header written by a newer version with an additional field.
+        let mut fields = header.encode_fields();
+        fields.extend_from_slice(&[0xff, 2, 0, 0, 0, 0xab, 0xcd]);
+        assert_eq!(SnapshotHeader::decode_fields(&fields).unwrap(), header);
+
+        let mut buf = Vec::new();
+        write(&bs0, &mut buf, &key, &[]).unwrap();
+        let decoded = read_header(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded.compression, Compression::None);
+        assert_eq!(read(&mut buf.as_slice(), &key, &[]).unwrap(), bs0);
+    }
+
+    #[test]
+    fn test_snapshot_header_is_authenticated() {
+        let f = tempfile::tempdir().unwrap();
+        let mut pb = f.into_path();
+        pb.push("snapshot");
+
+        let key: Key = random_key();
+        let info = SnapshotInfo {
+            client_ids: vec![],
+            label: Some("label".into()),
+        };
+        write_snapshot(&random_bytestring(), &pb, &SnapshotKey::Key(key), info, &[]).unwrap();
+
+        // change the label to "lAbel".
+        let mut buf = std::fs::read(&pb).unwrap();
+        let pos = buf.windows(5).position(|w| w == b"label").unwrap();
+        buf[pos + 1] = b'A';
+        std::fs::write(&pb, &buf).unwrap();
+
+        assert_eq!(read_header_from(&pb).unwrap().info.label.as_deref(), Some("lAbel"));
+        assert!(read_from(&pb, &key, &[]).is_err());
+    }
+
     struct TestVector {
         key: &'static str,
         ad: &'static str,
@@ -371,6 +970,10 @@ mod test {
             let data = hex::decode(tv.data).unwrap();
             let snapshot = hex::decode(tv.snapshot).unwrap();
 
+            let header = read_header(&mut snapshot.as_slice()).unwrap();
+            assert_eq!(header.version, OLD_VERSION);
+            assert_eq!(header.created_at, None);
+
             let pt = read(&mut snapshot.as_slice(), &key, &ad).unwrap();
 
             assert_eq!(pt, data);
@@ -25,5 +25,5 @@ pub use crate::vault::{
     base64::{Base64Decodable, Base64Encodable},
     crypto_box::{BoxProvider, Decrypt, Encrypt, Key},
     types::utils::{ChainId, ClientId, Id, InvalidLength, RecordHint, RecordId, VaultId},
-    view::{DbView, RecordError, VaultError},
+    view::{DbView, LegacyDbView, RecordError, VaultError},
 };
@@ -68,6 +68,68 @@ pub struct Record {
     revoke: Option<SealedTransaction>,
     /// encrypted data in blob format.
     blob: SealedBlob,
+    /// encrypted metadata of the record.
+    meta: Option<SealedBlob>,
+}
+
+/// Associated data under which the metadata of a [`Record`] is sealed, next to its [`ChainId`].
+const METADATA_AD: &[u8] = b"metadata";
+
+/// Layout of a [`DbView`] in snapshots of version 2.0, before records had metadata.
+#[derive(Deserialize)]
+pub struct LegacyDbView<P: BoxProvider> {
+    vaults: HashMap<VaultId, LegacyVault<P>>,
+}
+
+#[derive(Deserialize)]
+struct LegacyVault<P: BoxProvider> {
+    key: Key<P>,
+    entries: HashMap<ChainId, LegacyRecord>,
+}
+
+#[derive(Deserialize)]
+struct LegacyRecord {
+    id: ChainId,
+    data: SealedTransaction,
+    revoke: Option<SealedTransaction>,
+    blob: SealedBlob,
+}
+
+impl<P: BoxProvider> From<LegacyDbView<P>> for DbView<P> {
+    fn from(legacy: LegacyDbView<P>) -> Self {
+        let vaults = legacy
+            .vaults
+            .into_iter()
+            .map(|(vid, vault)| {
+                let entries = vault
+                    .entries
+                    .into_iter()
+                    .map(|(id, record)| {
+                        let LegacyRecord {
+                            id: rid,
+                            data,
+                            revoke,
+                            blob,
+                        } = record;
+                        let record = Record {
+                            id: rid,
+                            data,
+                            revoke,
+                            blob,
+                            meta: None,
+                        };
+                        (id, record)
+                    })
+                    .collect();
+                let vault = Vault {
+                    key: vault.key,
+                    entries,
+                };
+                (vid, vault)
+            })
+            .collect();
+        DbView { vaults }
+    }
 }
 
 impl<P: BoxProvider> DbView<P> {
@@ -162,6 +224,31 @@ impl<P: BoxProvider> DbView<P> {
         self.write(key1, vid1, rid1, &data, hint).map_err(VaultError::Record)
     }
 
+    /// Gets the decrypted metadata of the specified [`Record`], or `None` if no metadata was set.
+    pub fn get_metadata(
+        &self,
+        key: &Key<P>,
+        vid: VaultId,
+        rid: RecordId,
+    ) -> Result<Option<Vec<u8>>, VaultError<P::Error>> {
+        let vault = self.vaults.get(&vid).ok_or(VaultError::VaultNotFound(vid))?;
+        let metadata = vault.get_metadata(key, rid.0)?;
+        Ok(metadata)
+    }
+
+    /// Sets the metadata of the specified [`Record`]. The metadata is encrypted under the key of the [`Vault`].
+    pub fn set_metadata(
+        &mut self,
+        key: &Key<P>,
+        vid: VaultId,
+        rid: RecordId,
+        metadata: &[u8],
+    ) -> Result<(), VaultError<P::Error>> {
+        let vault = self.vaults.get_mut(&vid).ok_or(VaultError::VaultNotFound(vid))?;
+        vault.set_metadata(key, rid.0, metadata)?;
+        Ok(())
+    }
+
     /// Add a revocation transaction to the [`Record`]
     pub fn revoke_record(&mut self, key: &Key<P>, vid: VaultId, rid: RecordId) -> Result<(), RecordError<P::Error>> {
         if let Some(vault) = self.vaults.get_mut(&vid) {
@@ -179,6 +266,15 @@ impl<P: BoxProvider> DbView<P> {
         }
     }
 
+    /// Rotates the key of a [`Vault`] to `new_key`. Every live [`Record`] is re-sealed under the new key while keeping
+    /// its [`RecordId`] and [`RecordHint`], revoked records are garbage collected. The vault is left unchanged if
+    /// re-sealing any of the records fails.
+    pub fn rotate_key(&mut self, key: &Key<P>, new_key: &Key<P>, vid: VaultId) -> Result<(), VaultError<P::Error>> {
+        let vault = self.vaults.get_mut(&vid).ok_or(VaultError::VaultNotFound(vid))?;
+        vault.rotate_key(key, new_key)?;
+        Ok(())
+    }
+
     /// Clears the entire [`Vault`] from memory.
     pub fn clear(&mut self) {
         self.vaults.clear();
@@ -265,6 +361,44 @@ impl<P: BoxProvider> Vault<P> {
         entry.get_blob(key, id)
     }
 
+    /// Gets the decrypted metadata of a [`Record`].
+    pub fn get_metadata(&self, key: &Key<P>, id: ChainId) -> Result<Option<Vec<u8>>, RecordError<P::Error>> {
+        if key != &self.key {
+            return Err(RecordError::InvalidKey);
+        }
+        let entry = self.entries.get(&id).ok_or(RecordError::RecordNotFound(id))?;
+        entry.get_meta(key)
+    }
+
+    /// Encrypts and sets the metadata of a [`Record`].
+    pub fn set_metadata(&mut self, key: &Key<P>, id: ChainId, metadata: &[u8]) -> Result<(), RecordError<P::Error>> {
+        if key != &self.key {
+            return Err(RecordError::InvalidKey);
+        }
+        let entry = self.entries.get_mut(&id).ok_or(RecordError::RecordNotFound(id))?;
+        entry.set_meta(key, metadata)
+    }
+
+    /// Re-seals all live records under `new_key` and replaces the key of the [`Vault`]. The new records are collected
+    /// first, so that the [`Vault`] is only modified once all of them were re-sealed successfully.
+    pub fn rotate_key(&mut self, key: &Key<P>, new_key: &Key<P>) -> Result<(), RecordError<P::Error>> {
+        if key != &self.key {
+            return Err(RecordError::InvalidKey);
+        }
+
+        let entries = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.revoke.is_none())
+            .map(|(id, entry)| Ok((*id, entry.reseal(key, new_key)?)))
+            .collect::<Result<HashMap<_, _>, RecordError<P::Error>>>()?;
+
+        self.entries = entries;
+        self.key = new_key.clone();
+
+        Ok(())
+    }
+
     /// Sorts through all of the vault entries and garbage collects any revoked entries.
     pub fn garbage_collect(&mut self) {
         // get the keys of the entries with the revocation transactions.
@@ -302,6 +436,7 @@ impl Record {
             data,
             blob,
             revoke: None,
+            meta: None,
         })
     }
 
@@ -393,6 +528,55 @@ impl Record {
         Ok(())
     }
 
+    /// Creates a copy of this [`Record`] that is sealed under `new_key`, with a fresh [`BlobId`].
+    fn reseal<P: BoxProvider>(&self, key: &Key<P>, new_key: &Key<P>) -> Result<Record, RecordError<P::Error>> {
+        let tx = self.get_transaction(key)?;
+        let tx = tx.typed::<DataTransaction>().ok_or_else(|| {
+            RecordError::CorruptedContent("Could not type decrypted transaction as data-transaction".into())
+        })?;
+
+        let data: Vec<u8> = self.blob.decrypt(key, tx.blob).map_err(|err| match err {
+            DecryptError::Invalid => RecordError::CorruptedContent("Could not decrypt blob".into()),
+            DecryptError::Provider(e) => RecordError::Provider(e),
+        })?;
+
+        let blob_id = BlobId::random::<P>().map_err(RecordError::Provider)?;
+        let mut record =
+            Record::new(new_key, self.id, blob_id, &data, tx.record_hint).map_err(RecordError::Provider)?;
+        if let Some(meta) = self.get_meta(key)? {
+            record.set_meta(new_key, &meta)?;
+        }
+        Ok(record)
+    }
+
+    /// Decrypts the metadata of this [`Record`]. Fails if the record was revoked.
+    fn get_meta<P: BoxProvider>(&self, key: &Key<P>) -> Result<Option<Vec<u8>>, RecordError<P::Error>> {
+        // fails for revoked records.
+        self.get_transaction(key)?;
+
+        let meta = match &self.meta {
+            Some(meta) => meta,
+            None => return Ok(None),
+        };
+        let ad = [self.id.as_ref(), METADATA_AD].concat();
+        let meta: Vec<u8> = meta.decrypt(key, ad).map_err(|err| match err {
+            DecryptError::Invalid => RecordError::CorruptedContent("Could not decrypt metadata".into()),
+            DecryptError::Provider(e) => RecordError::Provider(e),
+        })?;
+        Ok(Some(meta))
+    }
+
+    /// Encrypts and sets the metadata of this [`Record`]. Fails if the record was revoked.
+    fn set_meta<P: BoxProvider>(&mut self, key: &Key<P>, meta: &[u8]) -> Result<(), RecordError<P::Error>> {
+        // fails for revoked records.
+        self.get_transaction(key)?;
+
+        let ad = [self.id.as_ref(), METADATA_AD].concat();
+        let meta: SealedBlob = meta.encrypt(key, ad).map_err(RecordError::Provider)?;
+        self.meta = Some(meta);
+        Ok(())
+    }
+
     // add a revocation transaction to the [`Record`].
     fn revoke<P: BoxProvider>(&mut self, key: &Key<P>, id: ChainId) -> Result<(), RecordError<P::Error>> {
         // check if id and id match.
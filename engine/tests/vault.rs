@@ -142,3 +142,79 @@ fn test_vaults() {
     })
     .unwrap();
 }
+
+#[test]
+fn test_rotate_key() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key0 = Key::random();
+    let vid0 = VaultId::random::<Provider>().unwrap();
+    let rid0 = RecordId::random::<Provider>().unwrap();
+    let rid1 = RecordId::random::<Provider>().unwrap();
+
+    view.write(&key0, vid0, rid0, b"test0", RecordHint::new(b"hint0").unwrap())
+        .unwrap();
+    view.write(&key0, vid0, rid1, b"test1", RecordHint::new(b"hint1").unwrap())
+        .unwrap();
+    view.revoke_record(&key0, vid0, rid1).unwrap();
+
+    // rotating with a wrong key fails and leaves the vault untouched.
+    let key1 = Key::random();
+    assert!(view.rotate_key(&key1, &key1, vid0).is_err());
+    assert!(view.contains_record(&key0, vid0, rid0));
+
+    view.rotate_key(&key0, &key1, vid0).unwrap();
+
+    // the old key no longer grants access.
+    assert!(view.list_hints_and_ids(&key0, vid0).is_empty());
+    assert!(view.get_guard::<Infallible, _>(&key0, vid0, rid0, |_| Ok(())).is_err());
+
+    // ids and hints are unchanged, the revoked record was dropped.
+    let list = view.list_hints_and_ids(&key1, vid0);
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].0, rid0);
+    assert_eq!(list[0].1, RecordHint::new(b"hint0").unwrap());
+
+    view.get_guard::<Infallible, _>(&key1, vid0, rid0, |g| {
+        assert_eq!(b"test0", &(*g.borrow()));
+
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_metadata() {
+    let mut view: DbView<Provider> = DbView::new();
+
+    let key0 = Key::random();
+    let vid0 = VaultId::random::<Provider>().unwrap();
+    let rid0 = RecordId::random::<Provider>().unwrap();
+    let rid1 = RecordId::random::<Provider>().unwrap();
+
+    view.write(&key0, vid0, rid0, b"test0", RecordHint::new(b"hint0").unwrap())
+        .unwrap();
+    assert_eq!(view.get_metadata(&key0, vid0, rid0).unwrap(), None);
+    assert!(view.set_metadata(&key0, vid0, rid1, b"meta1").is_err());
+
+    view.set_metadata(&key0, vid0, rid0, b"meta0").unwrap();
+    assert_eq!(
+        view.get_metadata(&key0, vid0, rid0).unwrap().as_deref(),
+        Some(&b"meta0"[..])
+    );
+
+    // the metadata is kept when the record is updated and when the key is rotated.
+    view.write(&key0, vid0, rid0, b"test1", RecordHint::new(b"hint0").unwrap())
+        .unwrap();
+    let key1 = Key::random();
+    view.rotate_key(&key0, &key1, vid0).unwrap();
+    assert!(view.get_metadata(&key0, vid0, rid0).is_err());
+    assert_eq!(
+        view.get_metadata(&key1, vid0, rid0).unwrap().as_deref(),
+        Some(&b"meta0"[..])
+    );
+
+    // the metadata of revoked records is not accessible.
+    view.revoke_record(&key1, vid0, rid0).unwrap();
+    assert!(view.get_metadata(&key1, vid0, rid0).is_err());
+}
@@ -87,6 +87,7 @@ mod local_client {
                 ty: KeyType::Ed25519,
                 output: location,
                 hint: key_hint,
+                lifetime: None,
             };
             self.exec_proc(generate_key).await.map(|_| ())
         }
@@ -102,7 +103,7 @@ mod local_client {
                 msg: msg_bytes,
                 private_key: location,
             };
-            let signed = self.exec_proc(sign_message).await.map(|signed| signed.into())?;
+            let signed = Vec::<u8>::try_from(self.exec_proc(sign_message).await?)?;
             println!("Signed message: {:?}", signed);
             Ok(signed)
         }